@@ -0,0 +1,233 @@
+use std::fmt;
+use std::net::IpAddr;
+use url::{Host, Url};
+
+use crate::fnmatch::fnmatch;
+
+pub fn ip_in_network(ip: IpAddr, network: IpAddr, bits: u32) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) if bits <= 32 => {
+            let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+            (u32::from(ip) & mask) == (u32::from(network) & mask)
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) if bits <= 128 => {
+            let mask = u128::MAX.checked_shl(128 - bits).unwrap_or(0);
+            (u128::from(ip) & mask) == (u128::from(network) & mask)
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum HostPattern {
+    // "<local>", host name without dots
+    Local,
+    // "192.168.0.0/16"
+    Network { network: IpAddr, bits: u32 },
+    // "10.*" or "192.168.*.1", IPv4 addresses only
+    IpWildcard(String),
+    // "*.corp", lowercase, ".corp" is "*.corp"
+    Glob(String),
+}
+
+// One entry of a bypass list, in Windows ProxyOverride syntax:
+// "[scheme://]host[:port]", where host is "<local>", a CIDR block, an IP
+// wildcard or a host pattern with '*' and '?'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BypassRule {
+    pub(crate) scheme: Option<String>,
+    pub(crate) host: HostPattern,
+    pub(crate) port: Option<u16>,
+}
+
+impl BypassRule {
+    pub fn parse(rule: &str) -> Self {
+        let rule = rule.trim();
+        let (scheme, rest) = match rule.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, rule),
+        };
+        let rest = rest.trim_end_matches('/');
+
+        // CIDR block has no port
+        if let Some((network, bits)) = rest.split_once('/') {
+            let network = network.trim_start_matches('[').trim_end_matches(']');
+            if let (Ok(network), Ok(bits)) = (network.parse(), bits.parse()) {
+                return BypassRule {
+                    scheme,
+                    host: HostPattern::Network { network, bits },
+                    port: None,
+                };
+            }
+        }
+
+        let (host, port) = split_port(rest);
+        let host = host.to_ascii_lowercase();
+        let host = if host == "<local>" {
+            HostPattern::Local
+        } else if let Some(suffix) = host.strip_prefix('.') {
+            HostPattern::Glob(format!("*.{}", suffix))
+        } else if host.contains('*')
+            && host.contains('.')
+            && host
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '.' || c == '*')
+        {
+            HostPattern::IpWildcard(host)
+        } else {
+            HostPattern::Glob(host)
+        };
+        BypassRule { scheme, host, port }
+    }
+
+    // Rules from list, skipping empty entries
+    pub fn parse_list(rules: &[String]) -> Vec<Self> {
+        rules
+            .iter()
+            .filter(|rule| !rule.trim().is_empty())
+            .map(|rule| Self::parse(rule))
+            .collect()
+    }
+
+    pub fn matches(&self, url: &Url) -> bool {
+        if self
+            .scheme
+            .as_ref()
+            .is_some_and(|scheme| scheme != url.scheme())
+        {
+            return false;
+        }
+        if self.port.is_some() && self.port != url.port_or_known_default() {
+            return false;
+        }
+
+        let (host, ip) = match url.host() {
+            Some(Host::Domain(domain)) => {
+                let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+                let ip = domain.parse::<IpAddr>().ok();
+                (domain, ip)
+            }
+            Some(Host::Ipv4(ip)) => (ip.to_string(), Some(IpAddr::V4(ip))),
+            Some(Host::Ipv6(ip)) => (ip.to_string(), Some(IpAddr::V6(ip))),
+            None => return false,
+        };
+        match &self.host {
+            HostPattern::Local => ip.is_none() && !host.contains('.'),
+            HostPattern::Network { network, bits } => {
+                ip.is_some_and(|ip| ip_in_network(ip, *network, *bits))
+            }
+            HostPattern::IpWildcard(pattern) => {
+                matches!(ip, Some(IpAddr::V4(_))) && fnmatch(pattern, &host)
+            }
+            HostPattern::Glob(pattern) => fnmatch(pattern, &host),
+        }
+    }
+}
+
+// "host:port", "[::1]:port", or host alone, IPv6 address included
+fn split_port(s: &str) -> (&str, Option<u16>) {
+    if let Some((host, port)) = s.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+        return (host, port.strip_prefix(':').and_then(|p| p.parse().ok()));
+    }
+    match s.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (s, None),
+        },
+        _ => (s, None),
+    }
+}
+
+// Canonical form of rule, as shown in reports and lua bypass_list
+impl fmt::Display for BypassRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}://", scheme)?;
+        }
+        let host = match &self.host {
+            HostPattern::Local => "<local>".to_string(),
+            HostPattern::Network { network, bits } => format!("{}/{}", network, bits),
+            HostPattern::IpWildcard(pattern) | HostPattern::Glob(pattern) => pattern.clone(),
+        };
+        match self.port {
+            Some(port) if host.contains(':') => write!(f, "[{}]:{}", host, port),
+            Some(port) => write!(f, "{}:{}", host, port),
+            None => write!(f, "{}", host),
+        }
+    }
+}
+
+// First bypass rule matching URL, in canonical form
+pub fn find_bypass(bypass: &[BypassRule], url: &Url) -> Option<String> {
+    bypass
+        .iter()
+        .find(|rule| rule.matches(url))
+        .map(|rule| rule.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(rule: &str, url: &str) -> bool {
+        BypassRule::parse(rule).matches(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn test_bypass_rules() {
+        let cases = [
+            ("<local>", "http://intranet/", true),
+            ("<LOCAL>", "http://intranet:8080/", true),
+            ("<local>", "http://intranet.corp/", false),
+            ("<local>", "http://10.1.2.3/", false),
+            ("<local>", "http://[::1]/", false),
+            ("192.168.0.0/16", "http://192.168.10.1/", true),
+            ("192.168.0.0/16", "http://192.169.0.1/", false),
+            ("192.168.0.0/16", "http://host.corp/", false),
+            ("fd00::/8", "http://[fd12::1]/", true),
+            ("10.*", "http://10.1.2.3/", true),
+            ("10.*", "http://10.example.com/", false),
+            ("10.*", "http://11.1.2.3/", false),
+            ("*.corp", "http://CVS.Corp/", true),
+            ("*.CORP", "http://cvs.corp./", true),
+            ("*.corp", "http://corp/", false),
+            (".corp", "http://cvs.corp/", true),
+            ("*.corp:8443", "https://cvs.corp:8443/", true),
+            ("*.corp:8443", "https://cvs.corp/", false),
+            ("*.corp:443", "https://cvs.corp/", true),
+            ("http://*.intra", "http://www.intra/", true),
+            ("http://*.intra", "https://www.intra/", false),
+            ("[::1]:8080", "http://[::1]:8080/", true),
+            ("[::1]:8080", "http://[::1]/", false),
+            ("::1", "http://[::1]/", true),
+            ("localhost", "http://localhost/", true),
+        ];
+        for (rule, url, expected) in cases {
+            assert_eq!(matches(rule, url), expected, "{} {}", rule, url);
+        }
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        let cases = [
+            ("<Local>", "<local>"),
+            (" *.Corp ", "*.corp"),
+            (".corp", "*.corp"),
+            ("HTTP://*.intra/", "http://*.intra"),
+            ("*.corp:8443", "*.corp:8443"),
+            ("[::1]:8080", "[::1]:8080"),
+            ("192.168.1.0/16", "192.168.1.0/16"),
+        ];
+        for (rule, expected) in cases {
+            assert_eq!(BypassRule::parse(rule).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        let rules = BypassRule::parse_list(&["*.corp".into(), " ".into(), "<local>".into()]);
+        assert_eq!(rules.len(), 2);
+        let url = Url::parse("http://intranet/").unwrap();
+        assert_eq!(find_bypass(&rules, &url), Some("<local>".to_string()));
+    }
+}
@@ -0,0 +1,172 @@
+use rquickjs::Ctx;
+use std::error::Error;
+use std::fmt;
+
+use crate::parser::ProxyParseError;
+
+/// Why PAC script could not be read or downloaded
+#[derive(Debug)]
+pub enum PacLoadError {
+    Io(std::io::Error),
+    // Connection, DNS or TLS failure, or timeout
+    Transport(String),
+    // Invalid TLS settings, like unreadable CA bundle
+    Tls(String),
+    Status(u16),
+    TooLarge { limit: u64 },
+    Truncated,
+    Charset(String),
+    // Body without FindProxyForURL, like a captive portal page
+    NotPac,
+}
+
+impl fmt::Display for PacLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PacLoadError::Io(error) => write!(f, "{}", error),
+            PacLoadError::Transport(message) => write!(f, "{}", message),
+            PacLoadError::Tls(message) => write!(f, "TLS settings: {}", message),
+            PacLoadError::Status(status) => write!(f, "HTTP status {}", status),
+            PacLoadError::TooLarge { limit } => write!(f, "larger than {} bytes", limit),
+            PacLoadError::Truncated => write!(f, "truncated response"),
+            PacLoadError::Charset(charset) => write!(f, "unsupported charset {}", charset),
+            PacLoadError::NotPac => write!(f, "FindProxyForURL is not defined"),
+        }
+    }
+}
+
+impl Error for PacLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PacLoadError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Why proxy for a URL could not be resolved
+#[derive(Debug)]
+pub enum ResolveError {
+    InvalidUrl {
+        url: String,
+        source: url::ParseError,
+    },
+    PacUnavailable {
+        location: String,
+        source: PacLoadError,
+    },
+    MissingEntryPoint,
+    Script {
+        message: String,
+        stack: Option<String>,
+    },
+    InvalidAnswer {
+        answer: String,
+        position: usize,
+        error: ProxyParseError,
+    },
+    // Script interrupted after time limit
+    Timeout {
+        limit: std::time::Duration,
+    },
+}
+
+impl ResolveError {
+    // Error name in reports
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ResolveError::InvalidUrl { .. } => "invalid_url",
+            ResolveError::PacUnavailable { .. } => "pac_unavailable",
+            ResolveError::MissingEntryPoint => "missing_entry_point",
+            ResolveError::Script { .. } => "script",
+            ResolveError::InvalidAnswer { .. } => "invalid_answer",
+            ResolveError::Timeout { .. } => "timeout",
+        }
+    }
+
+    // Program exit code, distinct for each kind of error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ResolveError::InvalidUrl { .. } => 3,
+            ResolveError::PacUnavailable { .. } => 4,
+            ResolveError::MissingEntryPoint => 5,
+            ResolveError::Script { .. } => 6,
+            ResolveError::InvalidAnswer { .. } => 7,
+            ResolveError::Timeout { .. } => 10,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::InvalidUrl { url, source } => {
+                write!(f, "invalid URL {}: {}", url, source)
+            }
+            ResolveError::PacUnavailable { location, source } => {
+                write!(f, "could not load PAC script {}: {}", location, source)
+            }
+            ResolveError::MissingEntryPoint => write!(f, "missing FindProxyForURL in PAC script"),
+            ResolveError::Script { message, stack } => match stack {
+                Some(stack) if !stack.is_empty() => {
+                    write!(f, "PAC script error: {}\n{}", message, stack.trim_end())
+                }
+                _ => write!(f, "PAC script error: {}", message),
+            },
+            ResolveError::InvalidAnswer {
+                answer,
+                position,
+                error,
+            } => write!(
+                f,
+                "invalid PAC answer \"{}\" at position {}: {}",
+                answer, position, error
+            ),
+            ResolveError::Timeout { limit } => {
+                write!(f, "PAC script interrupted after {} ms", limit.as_millis())
+            }
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::InvalidUrl { source, .. } => Some(source),
+            ResolveError::PacUnavailable { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// Error creating script runtime or context, before any script runs
+pub(crate) fn engine_error(error: rquickjs::Error) -> ResolveError {
+    ResolveError::Script {
+        message: format!("could not create script engine: {}", error),
+        stack: None,
+    }
+}
+
+// Convert error from QuickJS, with pending exception if any
+pub(crate) fn script_error(ctx: &Ctx, error: rquickjs::Error) -> ResolveError {
+    if !error.is_exception() {
+        return ResolveError::Script {
+            message: error.to_string(),
+            stack: None,
+        };
+    }
+    let value = ctx.catch();
+    match value.as_exception() {
+        Some(exception) => ResolveError::Script {
+            message: exception.message().unwrap_or_default(),
+            stack: exception.stack(),
+        },
+        None => ResolveError::Script {
+            message: value
+                .as_string()
+                .and_then(|s| s.to_string().ok())
+                .unwrap_or_else(|| format!("{:?}", value)),
+            stack: None,
+        },
+    }
+}
@@ -0,0 +1,60 @@
+//! Resolve proxy for a URL, from system settings, a PAC script, a static
+//! proxy or proxy environment variables.
+//!
+//! ```
+//! use voluapt::{ProxySettings, ResolverBuilder};
+//!
+//! let settings = ProxySettings {
+//!     proxy_enable: true,
+//!     proxy_server: Some("http=proxy.corp:8080;https=secure.corp:8443".to_string()),
+//!     proxy_override: vec!["*.corp".to_string()],
+//!     ..Default::default()
+//! };
+//! let resolver = ResolverBuilder::new(settings).build()?;
+//!
+//! let directives = resolver.resolve("https://example.com/")?;
+//! assert_eq!(directives[0].to_string(), "PROXY secure.corp:8443");
+//! assert_eq!(resolver.find_proxy("http://cvs.corp/")?, "DIRECT");
+//! # Ok::<(), voluapt::ResolveError>(())
+//! ```
+//!
+//! Use [`ResolverBuilder::from_system`] for system proxy settings, and
+//! [`ProxySettings::auto_config_url`] for a PAC script, local file or URL.
+
+mod fnmatch;
+
+pub mod bypass;
+pub mod envproxy;
+pub mod pacgen;
+pub mod pacloader;
+pub mod parser;
+pub mod tls;
+pub mod wpad;
+
+// Modules of command line program
+#[cfg(feature = "cli")]
+pub mod lint;
+#[cfg(feature = "cli")]
+pub mod output;
+#[cfg(feature = "cli")]
+pub mod server;
+
+mod proxyjs;
+pub use proxyjs::{
+    PacEnvironment, ProxySettings, ProxySettingsError, get_proxy_settings, load_pac_script,
+    parse_pac_time,
+};
+
+mod error;
+pub use error::{PacLoadError, ResolveError};
+
+mod memo;
+pub use memo::CacheStats;
+
+mod resolver;
+pub use resolver::{
+    DirectResolver, PACResolver, PacCacheKey, ProxyResolver, Resolver, ResolverBuilder,
+    ScriptLimits, StaticResolver,
+};
+
+pub use parser::{ProxyDirective, ProxyKind, ProxyParseError};
@@ -0,0 +1,772 @@
+use std::fmt;
+use std::rc::Rc;
+
+use rquickjs::{Ctx, Function, Value};
+
+use crate::error::ResolveError;
+use crate::parser::ProxyParser;
+use crate::resolver::{PACResolver, ScriptLimits};
+
+/// Severity of a lint finding, errors break resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Mistake found in PAC script, at 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.column, self.severity, self.message
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Ident(String),
+    Str(String),
+    Number,
+    Regex,
+    Template,
+    Punct(char),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(&self.kind, Kind::Ident(ident) if ident == name)
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.kind == Kind::Punct(c)
+    }
+
+    fn ident(&self) -> Option<&str> {
+        match &self.kind {
+            Kind::Ident(ident) => Some(ident),
+            _ => None,
+        }
+    }
+}
+
+// Names followed by "(" that are not function calls
+const KEYWORDS: &[&str] = &[
+    "if",
+    "for",
+    "while",
+    "switch",
+    "catch",
+    "function",
+    "return",
+    "typeof",
+    "with",
+    "do",
+    "else",
+    "in",
+    "of",
+    "void",
+    "delete",
+    "instanceof",
+    "new",
+    "case",
+    "throw",
+];
+
+// A "/" after these tokens starts a regular expression, not a division
+fn regex_allowed(previous: Option<&Token>) -> bool {
+    match previous.map(|token| &token.kind) {
+        None => true,
+        Some(Kind::Punct(c)) => !matches!(c, ')' | ']' | '}'),
+        Some(Kind::Ident(ident)) => KEYWORDS.contains(&ident.as_str()),
+        Some(_) => false,
+    }
+}
+
+// Approximate JavaScript tokenizer: enough to find calls, declarations,
+// return statements and string literals, without comments
+fn tokenize(script: &str) -> Vec<Token> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut tokens: Vec<Token> = Vec::new();
+    let (mut i, mut line, mut column) = (0, 1, 1);
+
+    // Advance over n characters, counting lines
+    let advance = |i: &mut usize, line: &mut usize, column: &mut usize, n: usize| {
+        for _ in 0..n {
+            if chars.get(*i) == Some(&'\n') {
+                *line += 1;
+                *column = 1;
+            } else {
+                *column += 1;
+            }
+            *i += 1;
+        }
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (start_line, start_column) = (line, column);
+        let start = i;
+
+        if c.is_whitespace() {
+            advance(&mut i, &mut line, &mut column, 1);
+            continue;
+        }
+        if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            advance(&mut i, &mut line, &mut column, 2);
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            let n = 2.min(chars.len() - i);
+            advance(&mut i, &mut line, &mut column, n);
+            continue;
+        }
+
+        let kind = if c == '"' || c == '\'' || c == '`' {
+            let mut value = String::new();
+            advance(&mut i, &mut line, &mut column, 1);
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    value.push(match chars[i + 1] {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                    advance(&mut i, &mut line, &mut column, 2);
+                } else {
+                    value.push(chars[i]);
+                    advance(&mut i, &mut line, &mut column, 1);
+                }
+            }
+            let n = 1.min(chars.len() - i);
+            advance(&mut i, &mut line, &mut column, n);
+            if c == '`' {
+                Kind::Template
+            } else {
+                Kind::Str(value)
+            }
+        } else if c == '/' && regex_allowed(tokens.last()) {
+            let mut in_class = false;
+            advance(&mut i, &mut line, &mut column, 1);
+            while i < chars.len() && chars[i] != '\n' && (in_class || chars[i] != '/') {
+                match chars[i] {
+                    '\\' => advance(&mut i, &mut line, &mut column, 1),
+                    '[' => in_class = true,
+                    ']' => in_class = false,
+                    _ => {}
+                }
+                let n = 1.min(chars.len() - i);
+                advance(&mut i, &mut line, &mut column, n);
+            }
+            let n = 1.min(chars.len() - i);
+            advance(&mut i, &mut line, &mut column, n);
+            while i < chars.len() && chars[i].is_alphabetic() {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            Kind::Regex
+        } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|n| n.is_ascii_digit())) {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            Kind::Number
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            while i < chars.len()
+                && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+            {
+                advance(&mut i, &mut line, &mut column, 1);
+            }
+            Kind::Ident(chars[start..i].iter().collect())
+        } else {
+            advance(&mut i, &mut line, &mut column, 1);
+            Kind::Punct(c)
+        };
+        tokens.push(Token {
+            kind,
+            line: start_line,
+            column: start_column,
+        });
+    }
+    tokens
+}
+
+// Index of token closing the bracket opened at index open
+fn closing(tokens: &[Token], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token.kind {
+            Kind::Punct('(' | '[' | '{') => depth += 1,
+            Kind::Punct(')' | ']' | '}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Index of token opening the bracket closed at index close
+fn opening(tokens: &[Token], close: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in (0..=close).rev() {
+        match tokens[i].kind {
+            Kind::Punct(')' | ']' | '}') => depth += 1,
+            Kind::Punct('(' | '[' | '{') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Token ranges of call arguments, for call with "(" at index open
+fn arguments(tokens: &[Token], open: usize) -> Vec<&[Token]> {
+    let Some(close) = closing(tokens, open) else {
+        return vec![];
+    };
+    let mut args = Vec::new();
+    let (mut start, mut depth) = (open + 1, 0);
+    for i in open + 1..close {
+        match tokens[i].kind {
+            Kind::Punct('(' | '[' | '{') => depth += 1,
+            Kind::Punct(')' | ']' | '}') => depth -= 1,
+            Kind::Punct(',') if depth == 0 => {
+                args.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < close {
+        args.push(&tokens[start..close]);
+    }
+    args
+}
+
+// Names declared by script: functions, parameters, variables and catch
+// bindings, in any scope
+fn declared_names(tokens: &[Token]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut push = |token: &Token| {
+        if let Some(name) = token.ident() {
+            names.push(name.to_string());
+        }
+    };
+    for (i, token) in tokens.iter().enumerate() {
+        match token.ident() {
+            Some("function") => {
+                let mut j = i + 1;
+                if let Some(name) = tokens.get(j).filter(|t| t.ident().is_some()) {
+                    push(name);
+                    j += 1;
+                }
+                if tokens.get(j).is_some_and(|t| t.is_punct('(')) {
+                    let close = closing(tokens, j).unwrap_or(tokens.len());
+                    tokens[j + 1..close].iter().for_each(&mut push);
+                }
+            }
+            Some("catch") if tokens.get(i + 1).is_some_and(|t| t.is_punct('(')) => {
+                if let Some(name) = tokens.get(i + 2) {
+                    push(name);
+                }
+            }
+            Some("var" | "let" | "const") => {
+                let mut j = i + 1;
+                let mut depth = 0;
+                if let Some(name) = tokens.get(j) {
+                    push(name);
+                }
+                while j < tokens.len() {
+                    match tokens[j].kind {
+                        Kind::Punct('(' | '[' | '{') => depth += 1,
+                        Kind::Punct(')' | ']' | '}') if depth == 0 => break,
+                        Kind::Punct(')' | ']' | '}') => depth -= 1,
+                        Kind::Punct(';') if depth == 0 => break,
+                        Kind::Punct(',') if depth == 0 => {
+                            if let Some(name) = tokens.get(j + 1) {
+                                push(name);
+                            }
+                        }
+                        _ => {}
+                    }
+                    j += 1;
+                }
+            }
+            _ => {}
+        }
+        // Arrow function parameters, "x =>" or "(x, y) =>"
+        if token.is_punct('=') && tokens.get(i + 1).is_some_and(|t| t.is_punct('>')) && i > 0 {
+            if tokens[i - 1].is_punct(')') {
+                if let Some(open) = opening(tokens, i - 1) {
+                    tokens[open + 1..i - 1].iter().for_each(&mut push);
+                }
+            } else {
+                push(&tokens[i - 1]);
+            }
+        }
+    }
+    names
+}
+
+// Index of token after return statement at index start, none when the
+// statement ends its block
+fn after_return(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start + 1) {
+        match token.kind {
+            Kind::Punct('(' | '[' | '{') => depth += 1,
+            Kind::Punct(')' | ']' | '}') if depth == 0 => return None,
+            Kind::Punct(')' | ']' | '}') => depth -= 1,
+            Kind::Punct(';') if depth == 0 => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Return statement is the body of if, else, while or for without braces
+fn is_unbraced_body(tokens: &[Token], index: usize) -> bool {
+    match index.checked_sub(1).map(|i| &tokens[i]) {
+        Some(token) if token.is_ident("else") => true,
+        Some(token) if token.is_punct(')') => opening(tokens, index - 1)
+            .and_then(|open| open.checked_sub(1))
+            .is_some_and(|i| {
+                ["if", "while", "for"]
+                    .iter()
+                    .any(|keyword| tokens[i].is_ident(keyword))
+            }),
+        _ => false,
+    }
+}
+
+struct Linter<'a> {
+    tokens: &'a [Token],
+    findings: Vec<Finding>,
+}
+
+impl Linter<'_> {
+    fn report(&mut self, token: &Token, severity: Severity, message: String) {
+        self.findings.push(Finding {
+            line: token.line,
+            column: token.column,
+            severity,
+            message,
+        });
+    }
+
+    // Calls of names not defined by script, PAC functions nor built-ins
+    fn undefined_calls(&mut self, is_global: impl Fn(&str) -> bool) {
+        let tokens = self.tokens;
+        let declared = declared_names(tokens);
+        for (i, token) in tokens.iter().enumerate() {
+            let Some(name) = token.ident() else {
+                continue;
+            };
+            let called = tokens.get(i + 1).is_some_and(|t| t.is_punct('('));
+            let member =
+                i > 0 && (tokens[i - 1].is_punct('.') || tokens[i - 1].is_ident("function"));
+            if called
+                && !member
+                && !KEYWORDS.contains(&name)
+                && !declared.iter().any(|d| d == name)
+                && !is_global(name)
+            {
+                self.report(
+                    token,
+                    Severity::Error,
+                    format!("call to undefined function {}", name),
+                );
+            }
+        }
+    }
+
+    // dnsDomainIs(host, "corp.com") also matches "evilcorp.com"
+    fn dns_domain_without_dot(&mut self) {
+        let tokens = self.tokens;
+        for (i, token) in tokens.iter().enumerate() {
+            if !token.is_ident("dnsDomainIs") || !tokens.get(i + 1).is_some_and(|t| t.is_punct('('))
+            {
+                continue;
+            }
+            if let Some([domain]) = arguments(tokens, i + 1).get(1)
+                && let Kind::Str(value) = &domain.kind
+                && !value.starts_with('.')
+            {
+                self.report(
+                    domain,
+                    Severity::Warning,
+                    format!(
+                        "dnsDomainIs domain \"{}\" without leading dot also matches \"x{}\", use \".{}\"",
+                        value, value, value
+                    ),
+                );
+            }
+        }
+    }
+
+    // isInNet(host, ...) resolves host name with DNS at each call
+    fn is_in_net_with_host_name(&mut self, host_param: &str) {
+        let tokens = self.tokens;
+        for (i, token) in tokens.iter().enumerate() {
+            let Some(name @ ("isInNet" | "isInNetEx")) = token.ident() else {
+                continue;
+            };
+            if !tokens.get(i + 1).is_some_and(|t| t.is_punct('(')) {
+                continue;
+            }
+            if let Some([host]) = arguments(tokens, i + 1).first()
+                && host.is_ident(host_param)
+            {
+                self.report(
+                    host,
+                    Severity::Warning,
+                    format!(
+                        "{} with host name \"{}\" runs a DNS lookup for each test, resolve it once with dnsResolve({})",
+                        name, host_param, host_param
+                    ),
+                );
+            }
+        }
+    }
+
+    // Statements after return, in the same block
+    fn unreachable_code(&mut self) {
+        let tokens = self.tokens;
+        for (i, token) in tokens.iter().enumerate() {
+            if !token.is_ident("return") || is_unbraced_body(tokens, i) {
+                continue;
+            }
+            let Some(next) = after_return(tokens, i).and_then(|j| tokens.get(j)) else {
+                continue;
+            };
+            // Function declarations are hoisted
+            if next.is_punct('}')
+                || ["case", "default", "function"]
+                    .iter()
+                    .any(|k| next.is_ident(k))
+            {
+                continue;
+            }
+            let next = next.clone();
+            self.report(
+                &next,
+                Severity::Warning,
+                "unreachable code after return".to_string(),
+            );
+        }
+    }
+
+    // String literals returned as is, like "PROXY proxy:8080; DIRECT"
+    fn literal_answers(&mut self) {
+        let tokens = self.tokens;
+        for (i, token) in tokens.iter().enumerate() {
+            let Kind::Str(answer) = &token.kind else {
+                continue;
+            };
+            let returned = i > 0
+                && (tokens[i - 1].is_ident("return")
+                    || tokens[i - 1].is_punct('?')
+                    || tokens[i - 1].is_punct(':'))
+                && tokens.get(i + 1).is_none_or(|next| {
+                    next.is_punct(';')
+                        || next.is_punct('}')
+                        || next.is_punct(':')
+                        || next.line > token.line
+                });
+            // Object keys and switch cases are not answers
+            let in_return = returned
+                && tokens[..i]
+                    .iter()
+                    .rev()
+                    .take_while(|t| !t.is_punct(';') && !t.is_punct('{') && !t.is_punct('}'))
+                    .any(|t| t.is_ident("return"));
+            if !in_return {
+                continue;
+            }
+            let mut parser = ProxyParser::new(answer);
+            if let Err(error) = parser.parse() {
+                let error = ResolveError::InvalidAnswer {
+                    position: parser.position(),
+                    answer: answer.clone(),
+                    error,
+                };
+                self.report(token, Severity::Error, error.to_string());
+            }
+        }
+    }
+}
+
+// Line and column of error in QuickJS stack, like "at eval_script:3:10"
+fn script_location(stack: Option<&str>) -> (usize, usize) {
+    let location = stack
+        .and_then(|stack| stack.split("eval_script:").nth(1))
+        .map(|rest| {
+            rest.split(|c: char| !c.is_ascii_digit() && c != ':')
+                .next()
+                .unwrap_or("")
+        })
+        .unwrap_or("");
+    let mut numbers = location.split(':').map(|n| n.parse().unwrap_or(1));
+    (numbers.next().unwrap_or(1), numbers.next().unwrap_or(1))
+}
+
+// Parameter count of global function, none when not a function
+fn arity(ctx: &Ctx, name: &str) -> Option<usize> {
+    let value: Value = ctx.globals().get(name).ok()?;
+    let function: Function = value.into_function()?;
+    function.get::<_, usize>("length").ok()
+}
+
+/// Mistakes in PAC script, evaluated in PAC context then checked statically:
+/// entry point arity, undefined functions, `dnsDomainIs` domains without
+/// leading dot, `isInNet` with host names, unreachable code after `return`,
+/// and literal answers. With strict `limits`, unavailable globals are
+/// undefined.
+pub fn lint_pac(script: &str, limits: ScriptLimits) -> Vec<Finding> {
+    let tokens = tokenize(script);
+    let mut linter = Linter {
+        tokens: &tokens,
+        findings: Vec::new(),
+    };
+    let resolver = PACResolver::with_limits(
+        "lint",
+        script.to_string(),
+        vec![],
+        Rc::default(),
+        false,
+        limits,
+    );
+    let start = Token {
+        kind: Kind::Number,
+        line: 1,
+        column: 1,
+    };
+
+    match resolver {
+        Ok(resolver) => resolver.with_context(|ctx| {
+            let entry_points: Vec<(&str, usize)> = ["FindProxyForURL", "FindProxyForURLEx"]
+                .into_iter()
+                .filter_map(|name| Some((name, arity(&ctx, name)?)))
+                .collect();
+            if entry_points.is_empty() {
+                linter.report(
+                    &start,
+                    Severity::Error,
+                    ResolveError::MissingEntryPoint.to_string(),
+                );
+            }
+            for (name, arity) in entry_points {
+                if arity != 2 {
+                    let location = tokens
+                        .windows(2)
+                        .find(|pair| pair[0].is_ident("function") && pair[1].is_ident(name))
+                        .map_or(&start, |pair| &pair[1]);
+                    linter.report(
+                        location,
+                        Severity::Error,
+                        format!(
+                            "{} takes {} parameters instead of 2 (url, host)",
+                            name, arity
+                        ),
+                    );
+                }
+            }
+            linter.undefined_calls(|name| {
+                ctx.globals()
+                    .get::<_, Value>(name)
+                    .is_ok_and(|value| !value.is_undefined())
+            });
+        }),
+        Err(error) => {
+            let (line, column) = match &error {
+                ResolveError::Script { stack, .. } => script_location(stack.as_deref()),
+                _ => (1, 1),
+            };
+            linter.findings.push(Finding {
+                line,
+                column,
+                severity: Severity::Error,
+                message: error.to_string().lines().next().unwrap_or("").to_string(),
+            });
+        }
+    }
+
+    // FindProxyForURL(url, host) parameter name for host
+    let host_param = tokens
+        .windows(6)
+        .find(|w| w[0].is_ident("function") && w[1].is_ident("FindProxyForURL"))
+        .and_then(|w| w[5].ident())
+        .unwrap_or("host")
+        .to_string();
+    linter.dns_domain_without_dot();
+    linter.is_in_net_with_host_name(&host_param);
+    linter.unreachable_code();
+    linter.literal_answers();
+
+    let mut findings = linter.findings;
+    findings.sort_by_key(|f| (f.line, f.column, f.severity));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(script: &str) -> Vec<String> {
+        lint_pac(script, ScriptLimits::default())
+            .iter()
+            .map(Finding::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_clean_script() {
+        let script = r#"// Corporate proxy
+function FindProxyForURL(url, host) {
+    var ip = dnsResolve(host);
+    if (isPlainHostName(host) || dnsDomainIs(host, ".corp.com"))
+        return "DIRECT";
+    if (ip && isInNet(ip, "10.0.0.0", "255.0.0.0")) return 'DIRECT';
+    if (/^ftp:/.test(url)) return "PROXY ftp.corp.com:2121";
+    switch (url.substring(0, 5)) {
+        case "https":
+            return "HTTPS secure.corp.com:443; DIRECT";
+        default:
+            return host.length > 20 ? "PROXY a:8080" : "PROXY b:8080";
+    }
+}"#;
+        assert_eq!(messages(script), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_entry_point() {
+        assert_eq!(
+            messages("function FindProxy(url, host) { return 'DIRECT'; }"),
+            ["1:1: error: missing FindProxyForURL in PAC script"]
+        );
+        assert_eq!(
+            messages("\nfunction FindProxyForURL(url) { return 'DIRECT'; }"),
+            ["2:10: error: FindProxyForURL takes 1 parameters instead of 2 (url, host)"]
+        );
+        assert_eq!(
+            messages("function FindProxyForURL(url, host) {\n  return +;\n}"),
+            ["2:9: error: PAC script error: unexpected token in expression: ';'"]
+        );
+    }
+
+    #[test]
+    fn test_undefined_calls() {
+        let script = "function FindProxyForURL(url, host) {
+    function local(x) { return x; }
+    var check = function (y) { return y; }, other = (z) => z;
+    if (isResolvable(host) || local(host) || check(url) || other(1)) return 'DIRECT';
+    return url.toLowerCase() && require('1');
+}";
+        assert_eq!(
+            messages(script),
+            [
+                "4:9: error: call to undefined function isResolvable",
+                "5:33: error: call to undefined function require"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_common_mistakes() {
+        let script = "function FindProxyForURL(url, h) {
+    if (dnsDomainIs(h, 'corp.com')) return 'DIRECT';
+    if (isInNet(h, '10.0.0.0', '255.0.0.0')) return 'DIRECT';
+    return 'PROXY proxy:8080';
+    alert('never');
+}";
+        assert_eq!(
+            messages(script),
+            [
+                "2:24: warning: dnsDomainIs domain \"corp.com\" without leading dot also matches \"xcorp.com\", use \".corp.com\"",
+                "3:17: warning: isInNet with host name \"h\" runs a DNS lookup for each test, resolve it once with dnsResolve(h)",
+                "5:5: warning: unreachable code after return"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_literal_answers() {
+        let script = "function FindProxyForURL(url, host) {
+    var names = { 'PROXY a': 1 };
+    if (host == 'a') return 'PROXY proxy:abc';
+    if (host == 'b') return 'PROXY ' + host + ':8080';
+    return host == 'c' ? 'DIRECT; PROXY' : \"SOCKS5 socks:1080\";
+}";
+        assert_eq!(
+            messages(script),
+            [
+                "3:29: error: invalid PAC answer \"PROXY proxy:abc\" at position 12: invalid port",
+                "5:26: error: invalid PAC answer \"DIRECT; PROXY\" at position 13: missing address"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = tokenize("a = /\\/[/]x/g; // c\n/* d */ b = 1.5 / 2; s = 'it\\'s'");
+        let kinds: Vec<Kind> = tokens.into_iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                Kind::Ident("a".into()),
+                Kind::Punct('='),
+                Kind::Regex,
+                Kind::Punct(';'),
+                Kind::Ident("b".into()),
+                Kind::Punct('='),
+                Kind::Number,
+                Kind::Punct('/'),
+                Kind::Number,
+                Kind::Punct(';'),
+                Kind::Ident("s".into()),
+                Kind::Punct('='),
+                Kind::Str("it's".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Hits and misses of a cache, shown in verbose output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} hits, {} misses", self.hits, self.misses)
+    }
+}
+
+// Values computed once per key, kept for ttl. Zero ttl disables memoization.
+#[derive(Debug, Default)]
+pub(crate) struct Memo<V> {
+    ttl: Cell<Duration>,
+    entries: RefCell<HashMap<String, (Instant, V)>>,
+    stats: Cell<CacheStats>,
+}
+
+impl<V: Clone> Memo<V> {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Memo {
+            ttl: Cell::new(ttl),
+            entries: RefCell::default(),
+            stats: Cell::default(),
+        }
+    }
+
+    pub(crate) fn set_ttl(&self, ttl: Duration) {
+        self.ttl.set(ttl);
+        self.clear();
+    }
+
+    pub(crate) fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    // Fresh value for key, or value of compute, kept when successful. The
+    // memo is not borrowed while computing, compute may use it.
+    pub(crate) fn get_or_try_insert<E>(
+        &self,
+        key: &str,
+        compute: impl FnOnce() -> Result<V, E>,
+    ) -> Result<V, E> {
+        let ttl = self.ttl.get();
+        if ttl.is_zero() {
+            return compute();
+        }
+        let mut stats = self.stats.get();
+        let fresh = match self.entries.borrow().get(key) {
+            Some((expires, value)) if Instant::now() < *expires => Some(value.clone()),
+            _ => None,
+        };
+        if let Some(value) = fresh {
+            stats.hits += 1;
+            self.stats.set(stats);
+            return Ok(value);
+        }
+        stats.misses += 1;
+        self.stats.set(stats);
+
+        let value = compute()?;
+        let mut entries = self.entries.borrow_mut();
+        entries.retain(|_, (expires, _)| Instant::now() < *expires);
+        entries.insert(key.to_string(), (Instant::now() + ttl, value.clone()));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memo() {
+        let memo = Memo::new(Duration::from_secs(60));
+        let mut calls = 0;
+        for _ in 0..3 {
+            let value = memo.get_or_try_insert("a", || {
+                calls += 1;
+                Ok::<_, ()>(calls)
+            });
+            assert_eq!(value, Ok(1));
+        }
+        // Errors are not kept
+        assert_eq!(
+            memo.get_or_try_insert("b", || Err::<u32, _>("no")),
+            Err("no")
+        );
+        assert_eq!(memo.get_or_try_insert("b", || Ok::<_, ()>(2)), Ok(2));
+        assert_eq!(memo.stats(), CacheStats { hits: 2, misses: 3 });
+        assert_eq!(memo.stats().to_string(), "2 hits, 3 misses");
+    }
+
+    #[test]
+    fn test_memo_expiry() {
+        let memo = Memo::new(Duration::from_millis(10));
+        assert_eq!(memo.get_or_try_insert("a", || Ok::<_, ()>(1)), Ok(1));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(memo.get_or_try_insert("a", || Ok::<_, ()>(2)), Ok(2));
+
+        memo.set_ttl(Duration::ZERO);
+        assert_eq!(memo.get_or_try_insert("a", || Ok::<_, ()>(3)), Ok(3));
+        assert_eq!(memo.stats(), CacheStats { hits: 0, misses: 2 });
+    }
+}
@@ -0,0 +1,482 @@
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
+
+use crate::parser::ProxyDirective;
+use crate::{ResolveError, Resolver};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// PAC answer only, or URL and answer separated by a tab in batch mode
+    Text,
+    /// One JSON object per line
+    Json,
+    /// Tab separated values, with a header line
+    Tsv,
+}
+
+fn directives_json(result: &Result<Vec<ProxyDirective>, ResolveError>) -> Value {
+    match result {
+        Ok(directives) => directives
+            .iter()
+            .map(|d| {
+                json!({
+                    "kind": d.kind.keyword(),
+                    "host": if d.host.is_empty() { None } else { Some(&d.host) },
+                    "port": d.port,
+                })
+            })
+            .collect(),
+        Err(_) => json!([]),
+    }
+}
+
+fn error_json(result: &Result<Vec<ProxyDirective>, ResolveError>) -> Value {
+    match result {
+        Ok(_) => Value::Null,
+        Err(error) => json!({
+            "kind": error.kind(),
+            "message": error.to_string(),
+        }),
+    }
+}
+
+// Everything known about resolution of one URL
+pub struct Resolution {
+    pub url: String,
+    pub resolver: &'static str,
+    pub pac_source: Option<String>,
+    pub result: Result<Vec<ProxyDirective>, ResolveError>,
+    pub bypass: Option<String>,
+    pub elapsed: Duration,
+}
+
+impl Resolution {
+    pub fn new(resolver: &Resolver, url: &str) -> Self {
+        let start = Instant::now();
+        let result = resolver.resolve(url);
+        let elapsed = start.elapsed();
+
+        Resolution {
+            url: url.to_string(),
+            resolver: resolver.kind(),
+            pac_source: resolver.pac_source(),
+            result,
+            bypass: resolver.bypass_match(url),
+            elapsed,
+        }
+    }
+
+    fn answer(&self) -> String {
+        match &self.result {
+            Ok(directives) => crate::parser::format_directives(directives),
+            Err(_) => String::new(),
+        }
+    }
+
+    fn proxy_urls(&self) -> Vec<Option<String>> {
+        match &self.result {
+            Ok(directives) => directives.iter().map(|d| d.to_url()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    fn elapsed_ms(&self) -> f64 {
+        self.elapsed.as_secs_f64() * 1000.0
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "url": self.url,
+            "resolver": self.resolver,
+            "pac_source": self.pac_source,
+            "answer": self.result.as_ref().ok().map(|_| self.answer()),
+            "directives": directives_json(&self.result),
+            "proxy_urls": self.proxy_urls(),
+            "bypass": {
+                "matched": self.bypass.is_some(),
+                "pattern": self.bypass,
+            },
+            "elapsed_ms": self.elapsed_ms(),
+            "error": error_json(&self.result),
+        })
+    }
+
+    // Record in JSON or TSV format, PAC answer in text format
+    pub fn format(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => self.answer(),
+            OutputFormat::Json => self.to_json().to_string(),
+            OutputFormat::Tsv => self.to_tsv(),
+        }
+    }
+
+    pub const TSV_HEADER: &'static str =
+        "url\tresolver\tpac_source\tanswer\tproxy_urls\tbypass\telapsed_ms\terror";
+
+    pub fn to_tsv(&self) -> String {
+        let proxy_urls = self
+            .proxy_urls()
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect::<Vec<String>>()
+            .join(",");
+        let error = match &self.result {
+            Ok(_) => String::new(),
+            Err(error) => error.to_string(),
+        };
+
+        [
+            self.url.clone(),
+            self.resolver.to_string(),
+            self.pac_source.clone().unwrap_or_default(),
+            self.answer(),
+            proxy_urls,
+            self.bypass.clone().unwrap_or_default(),
+            format!("{:.3}", self.elapsed_ms()),
+            error,
+        ]
+        .iter()
+        .map(|field| field.replace(['\t', '\n', '\r'], " "))
+        .collect::<Vec<String>>()
+        .join("\t")
+    }
+}
+
+// Resolve URL from each line of input, skipping empty lines and comments,
+// and print "url<TAB>proxy", or a record in JSON or TSV format. Errors do not
+// stop resolution of next URL.
+// Returns count of resolved and failed URL, or error writing output.
+pub fn run_batch(
+    resolver: &Resolver,
+    input: impl BufRead,
+    output: &mut impl Write,
+    format: OutputFormat,
+) -> io::Result<(usize, usize)> {
+    let (mut resolved, mut failed) = (0, 0);
+    if format == OutputFormat::Tsv {
+        writeln!(output, "{}", Resolution::TSV_HEADER)?;
+    }
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!(" ** ERROR : {}", e);
+                failed += 1;
+                break;
+            }
+        };
+        let url = line.trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+        if format != OutputFormat::Text {
+            let resolution = Resolution::new(resolver, url);
+            writeln!(output, "{}", resolution.format(format))?;
+            if resolution.result.is_ok() {
+                resolved += 1;
+            } else {
+                failed += 1;
+            }
+            continue;
+        }
+        match resolver.find_proxy(url) {
+            Ok(proxy) => {
+                writeln!(output, "{}\t{}", url, proxy)?;
+                resolved += 1;
+            }
+            Err(error) => {
+                eprintln!(" ** ERROR : {}: {}", url, error);
+                failed += 1;
+            }
+        }
+    }
+    Ok((resolved, failed))
+}
+
+// Answers of old and new resolvers for one URL
+pub struct Comparison {
+    pub url: String,
+    pub old: Result<Vec<ProxyDirective>, ResolveError>,
+    pub new: Result<Vec<ProxyDirective>, ResolveError>,
+}
+
+impl Comparison {
+    pub fn new(old: &Resolver, new: &Resolver, url: &str) -> Self {
+        Comparison {
+            url: url.to_string(),
+            old: old.resolve(url),
+            new: new.resolve(url),
+        }
+    }
+
+    // PAC answer, or first line of error message
+    fn answer(result: &Result<Vec<ProxyDirective>, ResolveError>) -> String {
+        match result {
+            Ok(directives) => crate::parser::format_directives(directives),
+            Err(error) => {
+                let message = error.to_string();
+                format!("error: {}", message.lines().next().unwrap_or_default())
+            }
+        }
+    }
+
+    /// Directive lists differ, or errors differ.
+    pub fn changed(&self) -> bool {
+        match (&self.old, &self.new) {
+            (Ok(old), Ok(new)) => old != new,
+            (old, new) => Self::answer(old) != Self::answer(new),
+        }
+    }
+
+    fn side_json(result: &Result<Vec<ProxyDirective>, ResolveError>) -> Value {
+        json!({
+            "answer": result.as_ref().ok().map(|_| Self::answer(result)),
+            "directives": directives_json(result),
+            "error": error_json(result),
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "url": self.url,
+            "old": Self::side_json(&self.old),
+            "new": Self::side_json(&self.new),
+        })
+    }
+
+    pub const TSV_HEADER: &'static str = "url\told\tnew";
+
+    // "url<TAB>old<TAB>new" in text and TSV formats, or JSON record
+    pub fn format(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Json => self.to_json().to_string(),
+            OutputFormat::Text | OutputFormat::Tsv => [
+                self.url.clone(),
+                Self::answer(&self.old),
+                Self::answer(&self.new),
+            ]
+            .iter()
+            .map(|field| field.replace(['\t', '\n', '\r'], " "))
+            .collect::<Vec<String>>()
+            .join("\t"),
+        }
+    }
+}
+
+// Resolve URL from each line of input with old and new resolvers, skipping
+// empty lines and comments like run_batch, and print changed URL only.
+// Returns count of compared and changed URL, or error writing output.
+pub fn run_diff(
+    old: &Resolver,
+    new: &Resolver,
+    input: impl BufRead,
+    output: &mut impl Write,
+    format: OutputFormat,
+) -> io::Result<(usize, usize)> {
+    let (mut compared, mut changed) = (0, 0);
+    if format == OutputFormat::Tsv {
+        writeln!(output, "{}", Comparison::TSV_HEADER)?;
+    }
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!(" ** ERROR : {}", e);
+                break;
+            }
+        };
+        let url = line.trim();
+        if url.is_empty() || url.starts_with('#') {
+            continue;
+        }
+        let comparison = Comparison::new(old, new, url);
+        compared += 1;
+        if comparison.changed() {
+            writeln!(output, "{}", comparison.format(format))?;
+            changed += 1;
+        }
+    }
+    Ok((compared, changed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bypass::BypassRule;
+    use crate::{Resolver, StaticResolver};
+
+    fn resolver() -> Resolver {
+        Resolver::new(Box::new(StaticResolver {
+            proxy_server: "proxy.corp:8080".to_string(),
+            bypass: vec![BypassRule::parse("*.corp")],
+        }))
+    }
+
+    #[test]
+    fn test_json_proxy() {
+        let json = Resolution::new(&resolver(), "https://example.com/").to_json();
+        assert_eq!(json["url"], "https://example.com/");
+        assert_eq!(json["resolver"], "static");
+        assert_eq!(json["pac_source"], Value::Null);
+        assert_eq!(json["answer"], "PROXY proxy.corp:8080");
+        assert_eq!(
+            json["directives"],
+            json!([{"kind": "PROXY", "host": "proxy.corp", "port": 8080}])
+        );
+        assert_eq!(json["proxy_urls"], json!(["http://proxy.corp:8080/"]));
+        assert_eq!(json["bypass"], json!({"matched": false, "pattern": null}));
+        assert!(json["elapsed_ms"].is_f64());
+        assert_eq!(json["error"], Value::Null);
+    }
+
+    #[test]
+    fn test_json_bypass() {
+        let json = Resolution::new(&resolver(), "http://intranet.corp/").to_json();
+        assert_eq!(
+            json["directives"],
+            json!([{"kind": "DIRECT", "host": null, "port": null}])
+        );
+        assert_eq!(json["proxy_urls"], json!([null]));
+        assert_eq!(
+            json["bypass"],
+            json!({"matched": true, "pattern": "*.corp"})
+        );
+    }
+
+    #[test]
+    fn test_json_error() {
+        let json = Resolution::new(&resolver(), "not a url").to_json();
+        assert_eq!(json["answer"], Value::Null);
+        assert_eq!(json["directives"], json!([]));
+        assert_eq!(json["error"]["kind"], "invalid_url");
+    }
+
+    #[test]
+    fn test_tsv() {
+        let tsv = Resolution::new(&resolver(), "https://example.com/").to_tsv();
+        let fields: Vec<&str> = tsv.split('\t').collect();
+        assert_eq!(fields.len(), Resolution::TSV_HEADER.split('\t').count());
+        assert_eq!(
+            fields[..6],
+            [
+                "https://example.com/",
+                "static",
+                "",
+                "PROXY proxy.corp:8080",
+                "http://proxy.corp:8080/",
+                ""
+            ]
+        );
+        assert_eq!(fields[7], "");
+    }
+
+    #[test]
+    fn test_run_batch() {
+        let resolver = resolver();
+        let input = "# audit\nhttps://example.com/\n\n  http://intranet.corp/x  \nnot a url\n";
+        let mut output = Vec::new();
+
+        let (resolved, failed) =
+            run_batch(&resolver, input.as_bytes(), &mut output, OutputFormat::Text).unwrap();
+
+        assert_eq!((resolved, failed), (2, 1));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "https://example.com/\tPROXY proxy.corp:8080\nhttp://intranet.corp/x\tDIRECT\n"
+        );
+    }
+
+    // Output closed by reader, like "head"
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_batch_closed_output() {
+        let input = "https://example.com/
+https://www.example.com/
+";
+        let error = run_batch(
+            &resolver(),
+            input.as_bytes(),
+            &mut ClosedPipe,
+            OutputFormat::Text,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn test_run_diff() {
+        let old = resolver();
+        let new = Resolver::new(Box::new(StaticResolver {
+            proxy_server: "proxy.corp:8080".to_string(),
+            bypass: vec![
+                BypassRule::parse("*.corp"),
+                BypassRule::parse("*.example.com"),
+            ],
+        }));
+        let input = "# corpus\nhttps://example.com/\nhttps://www.example.com/\nhttp://intranet.corp/\nnot a url\n";
+
+        let mut output = Vec::new();
+        let (compared, changed) = run_diff(
+            &old,
+            &new,
+            input.as_bytes(),
+            &mut output,
+            OutputFormat::Text,
+        )
+        .unwrap();
+        assert_eq!((compared, changed), (4, 1));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "https://www.example.com/\tPROXY proxy.corp:8080\tDIRECT\n"
+        );
+
+        let mut output = Vec::new();
+        run_diff(
+            &old,
+            &new,
+            input.as_bytes(),
+            &mut output,
+            OutputFormat::Json,
+        )
+        .unwrap();
+        let json: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(json["url"], "https://www.example.com/");
+        assert_eq!(json["old"]["answer"], "PROXY proxy.corp:8080");
+        assert_eq!(
+            json["new"]["directives"],
+            json!([{"kind": "DIRECT", "host": null, "port": null}])
+        );
+        assert_eq!(json["new"]["error"], Value::Null);
+    }
+
+    #[test]
+    fn test_comparison_error() {
+        let comparison = Comparison {
+            url: "http://example.com/".to_string(),
+            old: Ok(vec![]),
+            new: Err(ResolveError::MissingEntryPoint),
+        };
+        assert!(comparison.changed());
+        assert_eq!(
+            comparison.format(OutputFormat::Text),
+            "http://example.com/\t\terror: missing FindProxyForURL in PAC script"
+        );
+        assert_eq!(
+            comparison.to_json()["new"]["error"]["kind"],
+            "missing_entry_point"
+        );
+    }
+}
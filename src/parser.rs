@@ -0,0 +1,606 @@
+use std::fmt;
+use std::net::Ipv6Addr;
+
+fn is_alnum_or_hyphen(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ProxyParseError {
+    UnexpectedToken,
+    MissingAddress,
+    InvalidAddress,
+    InvalidPort,
+}
+
+impl fmt::Display for ProxyParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProxyParseError::UnexpectedToken => write!(f, "unexpected token"),
+            ProxyParseError::MissingAddress => write!(f, "missing address"),
+            ProxyParseError::InvalidAddress => write!(f, "invalid address"),
+            ProxyParseError::InvalidPort => write!(f, "invalid port"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Direct,
+    Proxy,
+    Https,
+    Socks,
+    Socks4,
+    Socks5,
+}
+
+impl ProxyKind {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword.to_ascii_uppercase().as_str() {
+            "DIRECT" => Some(ProxyKind::Direct),
+            "PROXY" | "HTTP" => Some(ProxyKind::Proxy),
+            "HTTPS" => Some(ProxyKind::Https),
+            "SOCKS" => Some(ProxyKind::Socks),
+            "SOCKS4" => Some(ProxyKind::Socks4),
+            "SOCKS5" => Some(ProxyKind::Socks5),
+            _ => None,
+        }
+    }
+
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ProxyKind::Direct => "DIRECT",
+            ProxyKind::Proxy => "PROXY",
+            ProxyKind::Https => "HTTPS",
+            ProxyKind::Socks => "SOCKS",
+            ProxyKind::Socks4 => "SOCKS4",
+            ProxyKind::Socks5 => "SOCKS5",
+        }
+    }
+
+    // URL scheme to reach proxy, none for DIRECT
+    pub fn scheme(&self) -> Option<&'static str> {
+        match self {
+            ProxyKind::Direct => None,
+            ProxyKind::Proxy => Some("http"),
+            ProxyKind::Https => Some("https"),
+            ProxyKind::Socks | ProxyKind::Socks4 => Some("socks4"),
+            ProxyKind::Socks5 => Some("socks5"),
+        }
+    }
+}
+
+// One entry of a PAC answer, like "DIRECT" or "PROXY proxy.corp:8080".
+// Host of IPv6 address is kept without brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyDirective {
+    pub kind: ProxyKind,
+    pub host: String,
+    pub port: Option<u16>,
+}
+
+impl ProxyDirective {
+    pub fn direct() -> Self {
+        ProxyDirective {
+            kind: ProxyKind::Direct,
+            host: String::new(),
+            port: None,
+        }
+    }
+
+    pub fn new(kind: ProxyKind, host: &str, port: Option<u16>) -> Self {
+        ProxyDirective {
+            kind,
+            host: host.to_string(),
+            port,
+        }
+    }
+
+    // "host:port", with brackets around IPv6 address
+    pub fn address(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match self.port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        }
+    }
+
+    pub fn to_url(&self) -> Option<String> {
+        self.kind
+            .scheme()
+            .map(|scheme| format!("{}://{}/", scheme, self.address()))
+    }
+}
+
+impl fmt::Display for ProxyDirective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ProxyKind::Direct => write!(f, "DIRECT"),
+            kind => write!(f, "{} {}", kind.keyword(), self.address()),
+        }
+    }
+}
+
+// Format directives as a PAC answer
+pub fn format_directives(directives: &[ProxyDirective]) -> String {
+    directives
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<String>>()
+        .join("; ")
+}
+
+pub struct ProxyParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> ProxyParser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        ProxyParser { input, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn advance(&mut self, expected: &str) -> bool {
+        if self.input[self.pos..].starts_with(expected) {
+            self.pos += expected.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn advance_while<F>(&mut self, mut predicate: F) -> bool
+    where
+        F: FnMut(char) -> bool,
+    {
+        let mut advanced = false;
+        while let Some(c) = self.peek() {
+            if predicate(c) {
+                self.pos += c.len_utf8();
+                advanced = true;
+            } else {
+                break;
+            }
+        }
+        advanced
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.advance_while(|c| c.is_whitespace());
+    }
+
+    fn only_padding_remains(&self) -> bool {
+        self.input[self.pos..]
+            .chars()
+            .all(|c| c.is_whitespace() || c == ';')
+    }
+
+    fn at_separator(&self) -> bool {
+        matches!(self.peek(), None | Some(';'))
+    }
+
+    // Position in input, where parsing stopped on error
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn parse_port(&mut self) -> Result<Option<u16>, ProxyParseError> {
+        if !self.advance(":") {
+            return Ok(None);
+        }
+        let start = self.pos;
+        if !self.advance_while(|c| c.is_ascii_digit()) {
+            return Err(ProxyParseError::InvalidPort);
+        }
+        self.input[start..self.pos]
+            .parse::<u16>()
+            .map(Some)
+            .map_err(|_| {
+                self.pos = start;
+                ProxyParseError::InvalidPort
+            })
+    }
+
+    fn parse_server_addr(&mut self) -> Result<(String, Option<u16>), ProxyParseError> {
+        let start = self.pos;
+
+        // Bracketed IPv6 address
+        if self.advance("[") {
+            self.advance_while(|c| c != ']' && c != ';' && !c.is_whitespace());
+            let address = &self.input[start + 1..self.pos];
+            if !self.advance("]") || address.parse::<Ipv6Addr>().is_err() {
+                self.pos = start;
+                return Err(ProxyParseError::InvalidAddress);
+            }
+            let port = self.parse_port()?;
+            return Ok((address.to_string(), port));
+        }
+
+        // IPv4 address or hostname
+        if !self.advance_while(|c| is_alnum_or_hyphen(c) || c == '.') {
+            return Err(ProxyParseError::MissingAddress);
+        }
+        let host = &self.input[start..self.pos];
+        if host.split('.').any(|label| label.is_empty()) {
+            self.pos = start;
+            return Err(ProxyParseError::InvalidAddress);
+        }
+        let port = self.parse_port()?;
+        Ok((host.to_string(), port))
+    }
+
+    fn parse_directive(&mut self) -> Result<ProxyDirective, ProxyParseError> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        self.advance_while(|c| c.is_ascii_alphanumeric());
+        let Some(kind) = ProxyKind::from_keyword(&self.input[start..self.pos]) else {
+            self.pos = start;
+            return Err(ProxyParseError::UnexpectedToken);
+        };
+
+        let directive = if kind == ProxyKind::Direct {
+            ProxyDirective::direct()
+        } else {
+            if !self.advance_while(|c| c.is_whitespace()) && !self.at_separator() {
+                self.pos = start;
+                return Err(ProxyParseError::UnexpectedToken);
+            }
+            let (host, port) = self.parse_server_addr()?;
+            ProxyDirective::new(kind, &host, port)
+        };
+
+        self.skip_whitespace();
+        if self.at_separator() {
+            Ok(directive)
+        } else {
+            Err(ProxyParseError::UnexpectedToken)
+        }
+    }
+
+    // Move after next separator, and any padding after it
+    fn skip_separators(&mut self) {
+        self.advance_while(|c| c == ';' || c.is_whitespace());
+    }
+
+    // Whole answer, fails on first invalid entry
+    pub fn parse(&mut self) -> Result<Vec<ProxyDirective>, ProxyParseError> {
+        let mut directives = vec![];
+        loop {
+            directives.push(self.parse_directive()?);
+            if self.only_padding_remains() {
+                return Ok(directives);
+            }
+            self.skip_separators();
+        }
+    }
+
+    // Each entry of answer, invalid entries do not stop parsing
+    pub fn parse_entries(&mut self) -> Vec<Result<ProxyDirective, ProxyParseError>> {
+        let mut entries = vec![];
+        loop {
+            let entry = self.parse_directive();
+            if entry.is_err() {
+                self.advance_while(|c| c != ';');
+            }
+            entries.push(entry);
+            if self.only_padding_remains() {
+                return entries;
+            }
+            self.skip_separators();
+        }
+    }
+}
+
+// URL of each proxy in answer, empty for DIRECT and invalid entries
+pub fn proxy_to_url(pac_response: &str) -> Vec<String> {
+    ProxyParser::new(pac_response)
+        .parse_entries()
+        .into_iter()
+        .map(|entry| entry.ok().and_then(|d| d.to_url()).unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxy(host: &str, port: Option<u16>) -> ProxyDirective {
+        ProxyDirective::new(ProxyKind::Proxy, host, port)
+    }
+
+    #[test]
+    fn test_direct_simple() {
+        assert_eq!(
+            ProxyParser::new("DIRECT").parse(),
+            Ok(vec![ProxyDirective::direct()])
+        );
+    }
+
+    #[test]
+    fn test_direct_with_whitespace() {
+        assert_eq!(
+            ProxyParser::new("   DIRECT   ").parse(),
+            Ok(vec![ProxyDirective::direct()])
+        );
+        assert_eq!(
+            ProxyParser::new("  DIRECT;;").parse(),
+            Ok(vec![ProxyDirective::direct()])
+        );
+    }
+
+    #[test]
+    fn test_proxy_ip_no_port() {
+        assert_eq!(
+            ProxyParser::new("PROXY 192.168.0.1").parse(),
+            Ok(vec![proxy("192.168.0.1", None)])
+        );
+    }
+
+    #[test]
+    fn test_proxy_ip_with_port() {
+        assert_eq!(
+            ProxyParser::new("PROXY 192.168.0.1:8080").parse(),
+            Ok(vec![proxy("192.168.0.1", Some(8080))])
+        );
+    }
+
+    #[test]
+    fn test_proxy_hostname_no_port() {
+        assert_eq!(
+            ProxyParser::new("PROXY proxy.example.com").parse(),
+            Ok(vec![proxy("proxy.example.com", None)])
+        );
+    }
+
+    #[test]
+    fn test_proxy_hostname_with_port() {
+        assert_eq!(
+            ProxyParser::new("PROXY proxy.example.com:8080").parse(),
+            Ok(vec![proxy("proxy.example.com", Some(8080))])
+        );
+    }
+
+    #[test]
+    fn test_trailing_junk_ignored() {
+        assert_eq!(
+            ProxyParser::new(" PROXY proxy.example.com:8080 ;;  ").parse(),
+            Ok(vec![proxy("proxy.example.com", Some(8080))])
+        );
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        assert_eq!(
+            ProxyParser::new("INVALID proxy.example.com").parse(),
+            Err(ProxyParseError::UnexpectedToken)
+        );
+    }
+
+    #[test]
+    fn test_proxy_hostname_with_invalid_port() {
+        assert_eq!(
+            ProxyParser::new("PROXY proxy.example.com:abc").parse(),
+            Err(ProxyParseError::InvalidPort)
+        );
+        assert_eq!(
+            ProxyParser::new("PROXY proxy.example.com:65536").parse(),
+            Err(ProxyParseError::InvalidPort)
+        );
+    }
+
+    #[test]
+    fn test_proxy_missing_hostname_or_ip() {
+        assert_eq!(
+            ProxyParser::new("PROXY ").parse(),
+            Err(ProxyParseError::MissingAddress)
+        );
+        assert_eq!(
+            ProxyParser::new("PROXY; DIRECT").parse(),
+            Err(ProxyParseError::MissingAddress)
+        );
+    }
+
+    #[test]
+    fn test_fallback_list() {
+        assert_eq!(
+            ProxyParser::new("PROXY a:8080; PROXY b:8080; DIRECT").parse(),
+            Ok(vec![
+                proxy("a", Some(8080)),
+                proxy("b", Some(8080)),
+                ProxyDirective::direct()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_all_kinds() {
+        assert_eq!(
+            ProxyParser::new("https h:443;socks s:1080;SOCKS4 s4:1080;Socks5 s5:1080;HTTP p:80")
+                .parse(),
+            Ok(vec![
+                ProxyDirective::new(ProxyKind::Https, "h", Some(443)),
+                ProxyDirective::new(ProxyKind::Socks, "s", Some(1080)),
+                ProxyDirective::new(ProxyKind::Socks4, "s4", Some(1080)),
+                ProxyDirective::new(ProxyKind::Socks5, "s5", Some(1080)),
+                proxy("p", Some(80)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_proxy_ipv6() {
+        assert_eq!(
+            ProxyParser::new("PROXY [2001:db8::1]:8080; SOCKS5 [::1]").parse(),
+            Ok(vec![
+                proxy("2001:db8::1", Some(8080)),
+                ProxyDirective::new(ProxyKind::Socks5, "::1", None),
+            ])
+        );
+        assert_eq!(
+            ProxyParser::new("PROXY [2001:db8::zz]:8080").parse(),
+            Err(ProxyParseError::InvalidAddress)
+        );
+        assert_eq!(
+            ProxyParser::new("PROXY [2001:db8::1:8080").parse(),
+            Err(ProxyParseError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn test_invalid_hostname() {
+        assert_eq!(
+            ProxyParser::new("PROXY proxy..corp:8080").parse(),
+            Err(ProxyParseError::InvalidAddress)
+        );
+        assert_eq!(
+            ProxyParser::new("PROXY proxy.corp.:8080").parse(),
+            Err(ProxyParseError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn test_missing_separator() {
+        assert_eq!(
+            ProxyParser::new("PROXY a:1 PROXY b:2").parse(),
+            Err(ProxyParseError::UnexpectedToken)
+        );
+        assert_eq!(
+            ProxyParser::new("DIRECTLY").parse(),
+            Err(ProxyParseError::UnexpectedToken)
+        );
+    }
+
+    #[test]
+    fn test_error_position() {
+        let mut parser = ProxyParser::new("PROXY a:1; PROXY b:99999");
+        assert_eq!(parser.parse(), Err(ProxyParseError::InvalidPort));
+        assert_eq!(parser.position(), 19);
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        assert_eq!(
+            ProxyParser::new("UNKNOWN format; PROXY valid:1;; DIRECT x; DIRECT").parse_entries(),
+            vec![
+                Err(ProxyParseError::UnexpectedToken),
+                Ok(proxy("valid", Some(1))),
+                Err(ProxyParseError::UnexpectedToken),
+                Ok(ProxyDirective::direct()),
+            ]
+        );
+        assert_eq!(
+            ProxyParser::new("").parse_entries(),
+            vec![Err(ProxyParseError::UnexpectedToken)]
+        );
+    }
+
+    #[test]
+    fn test_directive_display() {
+        let input = "PROXY a:1; HTTPS [::1]:443; SOCKS b; SOCKS4 c:2; SOCKS5 d:3; DIRECT";
+        let directives = ProxyParser::new(input).parse().unwrap();
+        assert_eq!(format_directives(&directives), input);
+    }
+
+    #[test]
+    fn test_directive_to_url() {
+        assert_eq!(ProxyDirective::direct().to_url(), None);
+        assert_eq!(
+            ProxyDirective::new(ProxyKind::Https, "::1", Some(443)).to_url(),
+            Some("https://[::1]:443/".to_string())
+        );
+        assert_eq!(
+            ProxyDirective::new(ProxyKind::Socks4, "s", Some(1080)).to_url(),
+            Some("socks4://s:1080/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_alnum_or_hyphen_function() {
+        assert!(is_alnum_or_hyphen('a'));
+        assert!(is_alnum_or_hyphen('Z'));
+        assert!(is_alnum_or_hyphen('9'));
+        assert!(is_alnum_or_hyphen('-'));
+        assert!(is_alnum_or_hyphen('_'));
+        assert!(!is_alnum_or_hyphen('!'));
+        assert!(!is_alnum_or_hyphen(' '));
+    }
+}
+
+#[cfg(test)]
+mod proxy_to_url_tests {
+    use super::*;
+
+    #[test]
+    fn test_direct() {
+        let input = "DIRECT";
+        let expected = vec!["".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_http_proxy() {
+        let input = "PROXY proxy.example.com:8080";
+        let expected = vec!["http://proxy.example.com:8080/".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_socks_proxy() {
+        let input = "SOCKS socks.example.com:1080";
+        let expected = vec!["socks4://socks.example.com:1080/".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_socks5_proxy() {
+        let input = "SOCKS5 socks5.example.com:1080";
+        let expected = vec!["socks5://socks5.example.com:1080/".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_fallback_list() {
+        let input = "PROXY a:1; SOCKS b:2; SOCKS5 c:3; DIRECT";
+        let expected = vec![
+            "http://a:1/".to_string(),
+            "socks4://b:2/".to_string(),
+            "socks5://c:3/".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_whitespace_and_case() {
+        let input = "  proxy proxy.com:3128 ;   socks5 myproxy:1080 ; Direct ";
+        let expected = vec![
+            "http://proxy.com:3128/".to_string(),
+            "socks5://myproxy:1080/".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_unrecognized_entry() {
+        let input = "UNKNOWN format; PROXY valid:1";
+        let expected = vec!["".to_string(), "http://valid:1/".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+
+    #[test]
+    fn test_empty_string() {
+        let input = "";
+        let expected = vec!["".to_string()];
+        assert_eq!(proxy_to_url(input), expected);
+    }
+}
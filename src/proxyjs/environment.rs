@@ -0,0 +1,271 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use super::proxyjs::{get_my_ip_addresses, resolve_dns_ex};
+use crate::memo::{CacheStats, Memo};
+
+// Clock, DNS and local addresses seen by PAC helpers.
+//
+// By default, everything comes from the system. Each part can be overridden
+// to evaluate a PAC script as if it was run at another time, from another
+// host or network. System DNS answers can be kept for a while, see
+// set_dns_ttl.
+#[derive(Debug, Default)]
+pub struct PacEnvironment {
+    at: RefCell<Option<DateTime<FixedOffset>>>,
+    my_ip: RefCell<Vec<IpAddr>>,
+    hosts: RefCell<HashMap<String, Vec<IpAddr>>>,
+    dns: Memo<Vec<IpAddr>>,
+    // Changed by each override, PAC answers computed before are stale
+    generation: Cell<u64>,
+}
+
+impl PacEnvironment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_time(&self, at: DateTime<FixedOffset>) {
+        self.at.replace(Some(at));
+        self.changed();
+    }
+
+    pub fn set_my_ip(&self, addresses: Vec<IpAddr>) {
+        self.my_ip.replace(addresses);
+        self.changed();
+    }
+
+    pub fn map_host(&self, host: &str, addresses: Vec<IpAddr>) {
+        self.changed();
+        self.hosts
+            .borrow_mut()
+            .insert(host.to_ascii_lowercase(), addresses);
+    }
+
+    // Add mappings from a "hosts" file content: "address name [aliases...]"
+    pub fn load_hosts(&self, content: &str) -> Result<(), String> {
+        self.changed();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut fields = line.split_whitespace();
+            let Some(address) = fields.next() else {
+                continue;
+            };
+            let address = address
+                .parse::<IpAddr>()
+                .map_err(|_| format!("line {}: invalid address \"{}\"", number + 1, address))?;
+            for host in fields {
+                let host = host.to_ascii_lowercase();
+                let mut hosts = self.hosts.borrow_mut();
+                let addresses = hosts.entry(host).or_default();
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Keep system DNS answers for ttl, zero (default) disables the cache
+    pub fn set_dns_ttl(&self, ttl: Duration) {
+        self.dns.set_ttl(ttl);
+    }
+
+    pub fn dns_stats(&self) -> CacheStats {
+        self.dns.stats()
+    }
+
+    fn changed(&self) {
+        self.generation.set(self.generation.get() + 1);
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    // Wall clock time, local or GMT
+    pub fn now(&self, gmt: bool) -> NaiveDateTime {
+        match (*self.at.borrow(), gmt) {
+            (Some(at), true) => at.naive_utc(),
+            (Some(at), false) => at.naive_local(),
+            (None, true) => Utc::now().naive_utc(),
+            (None, false) => Local::now().naive_local(),
+        }
+    }
+
+    // All addresses for host, mapped ones first, or system DNS
+    pub fn resolve(&self, host: &str) -> Result<Vec<IpAddr>, io::Error> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![ip]);
+        }
+        let host = host.to_ascii_lowercase();
+        if let Some(addresses) = self.hosts.borrow().get(&host) {
+            return Ok(addresses.clone());
+        }
+        self.dns.get_or_try_insert(&host, || resolve_dns_ex(&host))
+    }
+
+    // First IPv4 address of host
+    pub fn resolve_ipv4(&self, host: &str) -> Result<Option<String>, io::Error> {
+        Ok(self
+            .resolve(host)?
+            .into_iter()
+            .find(|ip| ip.is_ipv4())
+            .map(|ip| ip.to_string()))
+    }
+
+    pub fn my_ip_addresses(&self) -> Vec<IpAddr> {
+        let my_ip = self.my_ip.borrow();
+        if my_ip.is_empty() {
+            get_my_ip_addresses()
+        } else {
+            my_ip.clone()
+        }
+    }
+
+    // First IPv4 local address. Configured addresses without IPv4 give the
+    // first one, as asked, rather than no address at all.
+    pub fn my_ip_address(&self) -> Option<String> {
+        let addresses = self.my_ip_addresses();
+        let fixed = !self.my_ip.borrow().is_empty();
+        addresses
+            .iter()
+            .find(|ip| ip.is_ipv4())
+            .or(if fixed { addresses.first() } else { None })
+            .map(|ip| ip.to_string())
+    }
+}
+
+// Date and time as "2026-10-19T09:00", with optional seconds and UTC offset.
+// Without offset, time is local.
+pub fn parse_pac_time(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(s) {
+        return Ok(at);
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%:z", "%Y-%m-%dT%H:%M%:z"] {
+        if let Ok(at) = DateTime::parse_from_str(s, format) {
+            return Ok(at);
+        }
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+            return Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|at| at.fixed_offset())
+                .ok_or_else(|| format!("\"{}\" does not exist in local time zone", s));
+        }
+    }
+    Err(format!(
+        "invalid date and time \"{}\", expected YYYY-MM-DDTHH:MM",
+        s
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike};
+
+    #[test]
+    fn test_parse_pac_time_with_offset() {
+        let at = parse_pac_time("2026-10-19T09:00:00+02:00").unwrap();
+        assert_eq!(at.naive_local().hour(), 9);
+        assert_eq!(at.naive_utc().hour(), 7);
+        let at = parse_pac_time("2026-10-19T09:00-05:00").unwrap();
+        assert_eq!(at.naive_utc().hour(), 14);
+    }
+
+    #[test]
+    fn test_parse_pac_time_local() {
+        let at = parse_pac_time("2026-10-19T09:00").unwrap();
+        assert_eq!(at.naive_local().day(), 19);
+        assert_eq!(at.naive_local().hour(), 9);
+        assert_eq!(at.naive_local().minute(), 0);
+        assert!(parse_pac_time("2026-10-19 09:00:30").is_ok());
+        assert!(parse_pac_time("monday").is_err());
+    }
+
+    #[test]
+    fn test_fixed_time() {
+        let env = PacEnvironment::new();
+        env.set_time(parse_pac_time("2026-10-19T23:30:00-02:00").unwrap());
+        assert_eq!(env.now(false).day(), 19);
+        assert_eq!(env.now(false).hour(), 23);
+        assert_eq!(env.now(true).day(), 20);
+        assert_eq!(env.now(true).hour(), 1);
+    }
+
+    #[test]
+    fn test_mapped_hosts() {
+        let env = PacEnvironment::new();
+        env.map_host("Proxy.Corp", vec!["10.0.0.1".parse().unwrap()]);
+        env.load_hosts("# comment\n\n10.0.0.2 intranet intranet.corp # alias\n::1 intranet\n")
+            .unwrap();
+
+        assert_eq!(
+            env.resolve("proxy.corp").unwrap(),
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(
+            env.resolve("INTRANET").unwrap(),
+            vec![
+                "10.0.0.2".parse::<IpAddr>().unwrap(),
+                "::1".parse::<IpAddr>().unwrap()
+            ]
+        );
+        assert_eq!(
+            env.resolve_ipv4("intranet.corp").unwrap(),
+            Some("10.0.0.2".to_string())
+        );
+        assert_eq!(
+            env.resolve("192.168.1.1").unwrap(),
+            vec!["192.168.1.1".parse::<IpAddr>().unwrap()]
+        );
+        assert!(env.load_hosts("not-an-ip host").is_err());
+    }
+
+    #[test]
+    fn test_dns_cache() {
+        let env = PacEnvironment::new();
+        env.set_dns_ttl(Duration::from_secs(60));
+        for _ in 0..3 {
+            assert!(!env.resolve("localhost").unwrap().is_empty());
+        }
+        // Addresses and mapped hosts are not looked up
+        env.map_host("proxy.corp", vec!["10.0.0.1".parse().unwrap()]);
+        env.resolve("proxy.corp").unwrap();
+        env.resolve("10.0.0.2").unwrap();
+        assert_eq!(env.dns_stats(), CacheStats { hits: 2, misses: 1 });
+    }
+
+    #[test]
+    fn test_fixed_ip_address() {
+        let env = PacEnvironment::new();
+        env.set_my_ip(vec![
+            "fe80::1".parse().unwrap(),
+            "10.1.2.3".parse().unwrap(),
+        ]);
+        assert_eq!(env.my_ip_address(), Some("10.1.2.3".to_string()));
+        assert_eq!(env.my_ip_addresses().len(), 2);
+    }
+
+    #[test]
+    fn test_fixed_ipv6_address_only() {
+        let env = PacEnvironment::new();
+        env.set_my_ip(vec![
+            "2001:db8::1".parse().unwrap(),
+            "2001:db8::2".parse().unwrap(),
+        ]);
+        assert_eq!(env.my_ip_address(), Some("2001:db8::1".to_string()));
+    }
+}
@@ -1,9 +1,18 @@
 mod settings;
 pub use settings::*;
 
+mod environment;
+pub use environment::*;
+
+mod timerange;
+
+#[allow(clippy::module_inception)]
 mod proxyjs;
 pub use proxyjs::*;
 
+mod sandbox;
+pub use sandbox::*;
+
 #[cfg(windows)]
 mod windows;
 #[cfg(windows)]
@@ -1,26 +1,51 @@
-use chrono::{Datelike, Local, Timelike};
 use std::io;
-use std::io::ErrorKind;
-use std::{fs, net::ToSocketAddrs};
-
-use rquickjs::function::Func;
+use std::net::UdpSocket;
+use std::net::{IpAddr, ToSocketAddrs};
 
-use ureq::Agent;
+use rquickjs::function::{Func, Rest};
+use rquickjs::{Ctx, IntoJs, Value};
 
+use crate::bypass::ip_in_network;
+use crate::error::PacLoadError;
 use crate::fnmatch::fnmatch;
-use std::net::UdpSocket;
+use crate::pacloader::PacLoader;
+
+use super::environment::PacEnvironment;
+use super::timerange::*;
+use std::rc::Rc;
+
+// PAC helpers returning `false` instead of a string on failure
+struct StringOrFalse(Option<String>);
+
+impl<'js> IntoJs<'js> for StringOrFalse {
+    fn into_js(self, ctx: &Ctx<'js>) -> rquickjs::Result<Value<'js>> {
+        match self.0 {
+            Some(s) => s.into_js(ctx),
+            None => Ok(Value::new_bool(ctx.clone(), false)),
+        }
+    }
+}
 
-fn get_my_ip_address() -> Result<String, io::Error> {
+fn get_local_address(bind: &str, remote: &str) -> Result<IpAddr, io::Error> {
     // Trick: connect to a public IP to get the local IP (does not send packets)
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    let socket = UdpSocket::bind(bind)?;
 
     // Connect to a known remote address (no data is actually sent)
-    socket.connect("8.8.8.8:80")?;
+    socket.connect(remote)?;
 
-    // Step 3: Get the local socket address
-    let local_addr = socket.local_addr()?;
+    // Get the local socket address
+    Ok(socket.local_addr()?.ip())
+}
 
-    Ok(local_addr.ip().to_string())
+// Local addresses used for outgoing IPv6 and IPv4 traffic, in this order
+pub(crate) fn get_my_ip_addresses() -> Vec<IpAddr> {
+    [
+        ("[::]:0", "[2001:4860:4860::8888]:80"),
+        ("0.0.0.0:0", "8.8.8.8:80"),
+    ]
+    .iter()
+    .filter_map(|(bind, remote)| get_local_address(bind, remote).ok())
+    .collect()
 }
 
 fn is_in_net(ip: &str, pattern: &str, mask: &str) -> bool {
@@ -42,17 +67,52 @@ fn is_in_net(ip: &str, pattern: &str, mask: &str) -> bool {
     }
 }
 
+// Microsoft extension: match IPv4 or IPv6 address against a CIDR prefix
+fn is_in_net_ex(ip: &str, prefix: &str) -> bool {
+    let Some((network, bits)) = prefix.trim().split_once('/') else {
+        return false;
+    };
+    let (Ok(ip), Ok(network), Ok(bits)) = (
+        ip.trim().parse::<IpAddr>(),
+        network.parse::<IpAddr>(),
+        bits.parse::<u32>(),
+    ) else {
+        return false;
+    };
+    ip_in_network(ip, network, bits)
+}
+
+// Microsoft extension: IPv6 addresses first, then IPv4, each in ascending order
+fn sort_ip_address_list(list: &str) -> Option<String> {
+    let mut addresses = list
+        .split(';')
+        .map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .collect::<Option<Vec<IpAddr>>>()?;
+
+    addresses.sort_by_key(|ip| (ip.is_ipv4(), *ip));
+
+    Some(
+        addresses
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect::<Vec<String>>()
+            .join(";"),
+    )
+}
+
 fn dns_domain_is(host: &str, domain: &str) -> bool {
     host.ends_with(domain)
 }
 
-// DNS resolver using Windows API (supports IPv4 and IPv6)
-pub fn resolve_dns(host: &str) -> Result<Option<String>, io::Error> {
-    let addr_iter = (host, 0).to_socket_addrs()?;
-    for addr in addr_iter {
-        return Ok(Some(addr.ip().to_string()));
+// DNS resolver using system API (all IPv4 and IPv6 addresses)
+pub fn resolve_dns_ex(host: &str) -> Result<Vec<IpAddr>, io::Error> {
+    let mut addresses: Vec<IpAddr> = vec![];
+    for addr in (host, 0).to_socket_addrs()? {
+        if !addresses.contains(&addr.ip()) {
+            addresses.push(addr.ip());
+        }
     }
-    Ok(None)
+    Ok(addresses)
 }
 
 fn is_plain_host_name(host: &str) -> bool {
@@ -63,98 +123,44 @@ fn local_host_or_domain_is(host: &str, hostdom: &str) -> bool {
     host == hostdom || hostdom.starts_with(&format!("{}.", host))
 }
 
-fn weekday_range_js(args: &Vec<String>) -> bool {
-    let now = Local::now();
-    let current_day = now.weekday().num_days_from_sunday();
-    let days = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
-    let day_index = |d: &str| days.iter().position(|x| *x == d.to_uppercase());
-
-    match args.len() {
-        1 => day_index(&args[0]) == Some(current_day as usize),
-        2 => {
-            if let (Some(start), Some(end)) = (day_index(&args[0]), day_index(&args[1])) {
-                if start <= end {
-                    (start..=end).contains(&(current_day as usize))
-                } else {
-                    let mut range = (start..7).chain(0..=end);
-                    range.any(|d| d == current_day as usize)
-                }
-            } else {
-                false
-            }
-        }
-        _ => false,
-    }
+// Download or read PAC file, without cache
+pub fn load_pac_script(pac_url: &str) -> Result<String, PacLoadError> {
+    PacLoader::default().load(pac_url)
 }
 
-fn time_range_js(args: &Vec<u32>) -> bool {
-    let hour = Local::now().hour();
-    match args.len() {
-        1 => hour == args[0],
-        2 => {
-            let (start, end) = (args[0], args[1]);
-            if start <= end {
-                hour >= start && hour <= end
-            } else {
-                hour >= start || hour <= end
-            }
-        }
-        _ => false,
-    }
-}
-
-fn date_range_js(args: &Vec<u32>) -> bool {
-    let now = Local::now();
-    match args.len() {
-        1 => now.day() == args[0],
-        2 => now.month() == args[0] && now.day() == args[1],
-        3 => now.month() == args[0] && now.day() == args[1] && now.year() as u32 == args[2],
-        _ => false,
-    }
+fn join_args(args: &[PacArg]) -> String {
+    args.iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
-// Download or read PAC file
-// Handle crappy server response like zscloud that break connection
-pub fn load_pac_script(pac_url: &str) -> Option<String> {
-    if pac_url.starts_with("http") {
-        let agent = Agent::new();
-        let resp = agent.get(pac_url).call();
-        match resp {
-            Err(error) => {
-                println!("HTTP error: URL {}: {:?}", pac_url, error);
-                Err(error).ok()?
-            }
-            Ok(response) => {
-                // capture response to be able to respond for UnexpectedEof case
-                let mut buf = String::new();
-                match response.into_reader().read_to_string(&mut buf) {
-                    Ok(_) => Some(buf),
-                    Err(io_error) => {
-                        if io_error.kind() == ErrorKind::UnexpectedEof {
-                            // println!("Unexpected EOF: URL {}: {:?}", pac_url, io_error);
-                            Some(buf)
-                        } else {
-                            println!("IO Error: URL {}: {:?}", pac_url, io_error);
-                            Err(io_error).ok()?
-                        }
-                    }
-                }
-            }
-        }
-    } else if pac_url.starts_with("file://") {
-        let path = pac_url.trim_start_matches("file://");
-        fs::read_to_string(path).ok()
-    } else {
-        fs::read_to_string(pac_url).ok()
-    }
-}
+/// PAC functions registered by `bind_pac_methods`.
+pub const PAC_FUNCTIONS: &[&str] = &[
+    "dnsResolve",
+    "dnsResolveEx",
+    "dnsDomainIs",
+    "shExpMatch",
+    "myIpAddress",
+    "isInNet",
+    "myIpAddressEx",
+    "isInNetEx",
+    "sortIpAddressList",
+    "isPlainHostName",
+    "localHostOrDomainIs",
+    "weekdayRange",
+    "timeRange",
+    "dateRange",
+    "alert",
+];
 
-pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
+pub fn bind_pac_methods(globals: &rquickjs::Object, env: Rc<PacEnvironment>, trace: bool) {
     // Wrap closures with Func::from
+    let dns_env = env.clone();
     globals
         .set(
             "dnsResolve",
-            Func::from(move |host: String| match resolve_dns(&host) {
+            Func::from(move |host: String| match dns_env.resolve_ipv4(&host) {
                 Ok(Some(response)) => {
                     if trace {
                         eprintln!("dnsResolve: {} ({})", host, response);
@@ -177,6 +183,32 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
         )
         .unwrap();
 
+    let dns_env = env.clone();
+    globals
+        .set(
+            "dnsResolveEx",
+            Func::from(move |host: String| {
+                let response = match dns_env.resolve(&host) {
+                    Ok(addresses) => addresses
+                        .iter()
+                        .map(|ip| ip.to_string())
+                        .collect::<Vec<String>>()
+                        .join(";"),
+                    Err(e) => {
+                        if trace {
+                            eprintln!("dnsResolveEx: {} (error: {})", host, e);
+                        }
+                        return "".to_string();
+                    }
+                };
+                if trace {
+                    eprintln!("dnsResolveEx: {} ({})", host, response);
+                }
+                response
+            }),
+        )
+        .unwrap();
+
     globals
         .set(
             "dnsDomainIs",
@@ -203,19 +235,20 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
         )
         .unwrap();
 
+    let ip_env = env.clone();
     globals
         .set(
             "myIpAddress",
-            Func::from(move || match get_my_ip_address() {
-                Ok(ip) => {
+            Func::from(move || match ip_env.my_ip_address() {
+                Some(ip) => {
                     if trace {
                         eprintln!("myIpAddress: {}", ip);
                     }
                     ip
                 }
-                Err(e) => {
+                None => {
                     if trace {
-                        eprintln!("myIpAddress: [failed] {}", e);
+                        eprintln!("myIpAddress: [failed]");
                     }
                     "127.0.0.1".to_string()
                 }
@@ -236,6 +269,51 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
         )
         .unwrap();
 
+    let ip_env = env.clone();
+    globals
+        .set(
+            "myIpAddressEx",
+            Func::from(move || {
+                let response = ip_env
+                    .my_ip_addresses()
+                    .iter()
+                    .map(|ip| ip.to_string())
+                    .collect::<Vec<String>>()
+                    .join(";");
+                if trace {
+                    eprintln!("myIpAddressEx: {}", response);
+                }
+                response
+            }),
+        )
+        .unwrap();
+
+    globals
+        .set(
+            "isInNetEx",
+            Func::from(move |ip: String, prefix: String| {
+                let accepted = is_in_net_ex(&ip, &prefix);
+                if trace {
+                    eprintln!("isInNetEx: {} | {} ({})", ip, prefix, accepted);
+                }
+                accepted
+            }),
+        )
+        .unwrap();
+
+    globals
+        .set(
+            "sortIpAddressList",
+            Func::from(move |list: String| {
+                let sorted = sort_ip_address_list(&list);
+                if trace {
+                    eprintln!("sortIpAddressList: {} ({:?})", list, sorted);
+                }
+                StringOrFalse(sorted)
+            }),
+        )
+        .unwrap();
+
     globals
         .set(
             "isPlainHostName",
@@ -262,32 +340,30 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
         )
         .unwrap();
 
+    let clock_env = env.clone();
     globals
         .set(
             "weekdayRange",
-            Func::from(move |args: Vec<String>| {
-                let accepted = weekday_range_js(&args);
+            Func::from(move |args: Rest<PacArg>| {
+                let (range, gmt) = split_gmt(&args);
+                let accepted = weekday_range(range, clock_env.now(gmt));
                 if trace {
-                    let params = args.join(", ");
-                    eprintln!("weekdayRange: [{}] ({})", params, accepted);
+                    eprintln!("weekdayRange: [{}] ({})", join_args(&args), accepted);
                 }
                 accepted
             }),
         )
         .unwrap();
 
+    let clock_env = env.clone();
     globals
         .set(
             "timeRange",
-            Func::from(move |args: Vec<u32>| {
-                let accepted = time_range_js(&args);
+            Func::from(move |args: Rest<PacArg>| {
+                let (range, gmt) = split_gmt(&args);
+                let accepted = time_range(range, clock_env.now(gmt));
                 if trace {
-                    let params = args
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ");
-                    eprintln!("timeRange: [{}] ({})", params, accepted);
+                    eprintln!("timeRange: [{}] ({})", join_args(&args), accepted);
                 }
                 accepted
             }),
@@ -297,15 +373,11 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
     globals
         .set(
             "dateRange",
-            Func::from(move |args: Vec<u32>| {
-                let accepted = date_range_js(&args);
+            Func::from(move |args: Rest<PacArg>| {
+                let (range, gmt) = split_gmt(&args);
+                let accepted = date_range(range, env.now(gmt));
                 if trace {
-                    let params = args
-                        .iter()
-                        .map(|u| u.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ");
-                    eprintln!("dateRange: [{}] ({})", params, accepted);
+                    eprintln!("dateRange: [{}] ({})", join_args(&args), accepted);
                 }
                 accepted
             }),
@@ -321,3 +393,66 @@ pub fn bind_pac_methods(globals: &rquickjs::Object, trace: bool) {
         )
         .unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_in_net_ex_ipv4() {
+        assert!(is_in_net_ex("198.95.249.79", "198.95.0.0/16"));
+        assert!(is_in_net_ex("10.1.2.3", "10.0.0.0/8"));
+        assert!(is_in_net_ex("10.1.2.3", "10.1.2.3/32"));
+        assert!(is_in_net_ex("10.1.2.3", "0.0.0.0/0"));
+        assert!(!is_in_net_ex("198.96.0.1", "198.95.0.0/16"));
+        assert!(!is_in_net_ex("10.1.2.4", "10.1.2.3/32"));
+    }
+
+    #[test]
+    fn test_is_in_net_ex_ipv6() {
+        assert!(is_in_net_ex(
+            "3ffe:8311:ffff:abcd::1",
+            "3ffe:8311:ffff::/48"
+        ));
+        assert!(is_in_net_ex("::1", "::1/128"));
+        assert!(is_in_net_ex("2001:db8::1", "::/0"));
+        assert!(!is_in_net_ex("3ffe:8312::1", "3ffe:8311:ffff::/48"));
+        assert!(!is_in_net_ex("::2", "::1/128"));
+    }
+
+    #[test]
+    fn test_is_in_net_ex_invalid() {
+        // families must match
+        assert!(!is_in_net_ex("10.1.2.3", "::/0"));
+        assert!(!is_in_net_ex("::1", "0.0.0.0/0"));
+        // prefix length out of range or missing
+        assert!(!is_in_net_ex("10.1.2.3", "10.0.0.0/33"));
+        assert!(!is_in_net_ex("::1", "::/129"));
+        assert!(!is_in_net_ex("10.1.2.3", "10.0.0.0"));
+        // host names are not resolved
+        assert!(!is_in_net_ex("localhost", "127.0.0.0/8"));
+    }
+
+    #[test]
+    fn test_sort_ip_address_list() {
+        assert_eq!(
+            sort_ip_address_list("10.2.3.9;2001:4860:0:2001::68;::1;10.2.3.1"),
+            Some("::1;2001:4860:0:2001::68;10.2.3.1;10.2.3.9".to_string())
+        );
+        assert_eq!(
+            sort_ip_address_list(" 192.168.0.2 ; 192.168.0.1 "),
+            Some("192.168.0.1;192.168.0.2".to_string())
+        );
+        assert_eq!(
+            sort_ip_address_list("fe80::2;fe80::1"),
+            Some("fe80::1;fe80::2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_sort_ip_address_list_invalid() {
+        assert_eq!(sort_ip_address_list(""), None);
+        assert_eq!(sort_ip_address_list("10.0.0.1;;10.0.0.2"), None);
+        assert_eq!(sort_ip_address_list("10.0.0.1;example.com"), None);
+    }
+}
@@ -0,0 +1,205 @@
+use rquickjs::context::intrinsic::{Date, Eval, Json, Promise, RegExp, RegExpCompiler};
+use rquickjs::{Context, Ctx, Function, Runtime};
+
+use super::proxyjs::PAC_FUNCTIONS;
+
+// Built-ins of PAC scripts, besides base objects. Eval compiles the PAC script
+// itself, Promise backs async functions: without it QuickJS aborts on them.
+// No module loader is set, and pending jobs are never run.
+type PacIntrinsics = (Date, Eval, RegExpCompiler, RegExp, Json, Promise);
+
+/// ECMAScript 5 globals available to strict PAC scripts, with PAC functions.
+pub const STANDARD_GLOBALS: &[&str] = &[
+    "Object",
+    "Function",
+    "Array",
+    "String",
+    "Number",
+    "Boolean",
+    "Math",
+    "Date",
+    "RegExp",
+    "JSON",
+    "Error",
+    "EvalError",
+    "RangeError",
+    "ReferenceError",
+    "SyntaxError",
+    "TypeError",
+    "URIError",
+    "parseInt",
+    "parseFloat",
+    "isNaN",
+    "isFinite",
+    "decodeURI",
+    "decodeURIComponent",
+    "encodeURI",
+    "encodeURIComponent",
+    "escape",
+    "unescape",
+    "Infinity",
+    "NaN",
+    "undefined",
+];
+
+// Disable code generation from strings and job queue, then in strict mode
+// replace globals not allowed by getters throwing ReferenceError. Scripts may
+// still define their own globals with these names.
+const LOCK_DOWN: &str = r#"(function (allowed) {
+    var global = globalThis;
+    function disabled() {
+        throw new EvalError("code generation from strings is disabled in PAC scripts");
+    }
+    disabled.prototype = Function.prototype;
+    [function () {}, function* () {}, async function () {}, async function* () {}].forEach(
+        function (f) {
+            Object.defineProperty(Object.getPrototypeOf(f), "constructor", { value: disabled });
+        }
+    );
+    global.Function = disabled;
+    delete global.eval;
+    delete global.Promise;
+    delete global.queueMicrotask;
+    if (!allowed) return;
+    Object.getOwnPropertyNames(global).forEach(function (name) {
+        if (allowed.indexOf(name) != -1) return;
+        Object.defineProperty(global, name, {
+            get: function () {
+                throw new ReferenceError(name + " is not available to strict PAC scripts");
+            },
+            set: function (value) {
+                Object.defineProperty(global, name, {
+                    value: value,
+                    writable: true,
+                    enumerable: true,
+                    configurable: true,
+                });
+            },
+        });
+    });
+})"#;
+
+/// Context for PAC scripts, with ECMAScript built-ins only.
+pub fn pac_context(rt: &Runtime) -> rquickjs::Result<Context> {
+    Context::custom::<PacIntrinsics>(rt)
+}
+
+/// Remove `eval`, `Function` constructors, `Promise` and `queueMicrotask`
+/// from PAC context, after `bind_pac_methods`. Strict scripts are limited to
+/// [`STANDARD_GLOBALS`] and PAC functions.
+pub fn lock_down(ctx: &Ctx, strict: bool) -> rquickjs::Result<()> {
+    let lock_down: Function = ctx.eval(LOCK_DOWN)?;
+    let allowed = strict.then(|| {
+        STANDARD_GLOBALS
+            .iter()
+            .chain(PAC_FUNCTIONS)
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>()
+    });
+    lock_down.call((allowed,))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxyjs::bind_pac_methods;
+
+    // Evaluate expression in locked down context, error message on failure
+    fn eval(strict: bool, script: &str) -> Result<String, String> {
+        let rt = Runtime::new().unwrap();
+        let context = pac_context(&rt).unwrap();
+        context.with(|ctx| {
+            bind_pac_methods(&ctx.globals(), Default::default(), false);
+            lock_down(&ctx, strict).unwrap();
+            ctx.eval::<String, _>(script).map_err(|_| {
+                let exception = ctx.catch();
+                exception
+                    .as_exception()
+                    .and_then(|e| e.message())
+                    .unwrap_or_default()
+            })
+        })
+    }
+
+    #[test]
+    fn test_pac_functions() {
+        let rt = Runtime::new().unwrap();
+        let context = pac_context(&rt).unwrap();
+        context.with(|ctx| {
+            let names =
+                || -> Vec<String> { ctx.eval("Object.getOwnPropertyNames(globalThis)").unwrap() };
+            let before = names();
+            bind_pac_methods(&ctx.globals(), Default::default(), false);
+            let mut added: Vec<String> = names()
+                .into_iter()
+                .filter(|name| !before.contains(name))
+                .collect();
+            added.sort();
+            let mut expected = PAC_FUNCTIONS.to_vec();
+            expected.sort();
+            assert_eq!(added, expected);
+        });
+    }
+
+    #[test]
+    fn test_code_generation_disabled() {
+        for script in [
+            "eval('1')",
+            "Function('return 1')()",
+            "(function () {}).constructor('return 1')()",
+            "(function* () {}).constructor('yield 1')",
+            "(async function () {}).constructor('return 1')",
+        ] {
+            assert!(eval(false, script).is_err(), "{}", script);
+        }
+        assert_eq!(
+            eval(false, "(function () {}) instanceof Function ? 'yes' : 'no'"),
+            Ok("yes".to_string())
+        );
+    }
+
+    #[test]
+    fn test_exposed_globals() {
+        assert_eq!(
+            eval(
+                false,
+                "[typeof Promise, typeof queueMicrotask, typeof Map, typeof JSON].join()"
+            ),
+            Ok("undefined,undefined,undefined,object".to_string())
+        );
+        assert_eq!(
+            eval(
+                false,
+                "[typeof eval, typeof Proxy, typeof WeakRef, typeof Uint8Array, typeof BigInt, \
+                 typeof performance].join()"
+            ),
+            Ok("undefined,undefined,undefined,undefined,undefined,undefined".to_string())
+        );
+        // async functions still run, without job queue
+        assert_eq!(
+            eval(false, "(async function () { return 1; })(); 'ok'"),
+            Ok("ok".to_string())
+        );
+    }
+
+    #[test]
+    fn test_strict_globals() {
+        assert_eq!(
+            eval(true, "Symbol('a')"),
+            Err("Symbol is not available to strict PAC scripts".to_string())
+        );
+        assert_eq!(eval(true, "JSON.stringify({})"), Ok("{}".to_string()));
+        assert_eq!(
+            eval(
+                true,
+                "typeof shExpMatch + typeof isInNetEx + typeof Date + typeof parseInt"
+            ),
+            Ok("functionfunctionfunctionfunction".to_string())
+        );
+        // Script globals shadow unavailable ones
+        assert_eq!(
+            eval(true, "var Symbol = 'own'; Symbol"),
+            Ok("own".to_string())
+        );
+    }
+}
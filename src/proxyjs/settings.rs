@@ -1,5 +1,8 @@
+#[derive(Debug, Default, PartialEq)]
 pub struct ProxySettings {
     pub auto_config_url: Option<String>,
+    // Web Proxy Auto-Discovery, when auto_config_url is not set
+    pub auto_detect: bool,
     pub proxy_enable: bool,
     pub proxy_server: Option<String>,
     pub proxy_override: Vec<String>,
@@ -0,0 +1,398 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use std::fmt;
+
+use rquickjs::{Ctx, FromJs, Value};
+
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+// Argument of weekdayRange, dateRange and timeRange: PAC scripts mix numbers
+// and names, and sometimes give numbers as strings
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacArg {
+    Number(i64),
+    Name(String),
+}
+
+impl<'js> FromJs<'js> for PacArg {
+    fn from_js(_ctx: &Ctx<'js>, value: Value<'js>) -> rquickjs::Result<Self> {
+        if let Some(number) = value.as_number() {
+            return Ok(PacArg::Number(number as i64));
+        }
+        let text = match value.as_string() {
+            Some(s) => s.to_string()?,
+            None => String::new(),
+        };
+        Ok(match text.trim().parse::<i64>() {
+            Ok(number) => PacArg::Number(number),
+            Err(_) => PacArg::Name(text.trim().to_uppercase()),
+        })
+    }
+}
+
+impl fmt::Display for PacArg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PacArg::Number(n) => write!(f, "{}", n),
+            PacArg::Name(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+// Remove trailing "GMT" argument, and tell if it was present
+pub fn split_gmt(args: &[PacArg]) -> (&[PacArg], bool) {
+    match args.split_last() {
+        Some((PacArg::Name(last), rest)) if last == "GMT" => (rest, true),
+        _ => (args, false),
+    }
+}
+
+// Inclusive range, wrapping around when start is after end
+fn in_range<T: PartialOrd>(start: T, end: T, current: T) -> bool {
+    if start <= end {
+        start <= current && current <= end
+    } else {
+        current >= start || current <= end
+    }
+}
+
+fn weekday_index(arg: &PacArg) -> Option<u32> {
+    match arg {
+        PacArg::Name(name) => WEEKDAYS.iter().position(|d| d == name).map(|i| i as u32),
+        PacArg::Number(_) => None,
+    }
+}
+
+// weekdayRange(wd1 [, wd2]), without the "GMT" argument
+pub fn weekday_range(args: &[PacArg], now: NaiveDateTime) -> bool {
+    let current = now.weekday().num_days_from_sunday();
+    match args {
+        [day] => weekday_index(day) == Some(current),
+        [start, end] => match (weekday_index(start), weekday_index(end)) {
+            (Some(start), Some(end)) => in_range(start, end, current),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn time_number(arg: &PacArg, max: i64) -> Option<u32> {
+    match arg {
+        PacArg::Number(n) if (0..=max).contains(n) => Some(*n as u32),
+        _ => None,
+    }
+}
+
+// timeRange(hour), timeRange(hour1, hour2),
+// timeRange(hour1, min1, hour2, min2),
+// timeRange(hour1, min1, sec1, hour2, min2, sec2),
+// without the "GMT" argument.
+//
+// An hour range ends before the last hour: timeRange(12, 13) is true from
+// noon to 12:59:59. Ranges with minutes or seconds include their upper bound.
+pub fn time_range(args: &[PacArg], now: NaiveDateTime) -> bool {
+    let limits = [23, 59, 59];
+    let numbers = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| {
+            let width = if args.len() > 2 { args.len() / 2 } else { 1 };
+            time_number(arg, limits[i % width])
+        })
+        .collect::<Option<Vec<u32>>>();
+    let Some(numbers) = numbers else {
+        return false;
+    };
+
+    let (hour, minute, second) = (now.hour(), now.minute(), now.second());
+    match numbers[..] {
+        [h] => hour == h,
+        [h1, h2] if h1 == h2 => hour == h1,
+        [h1, h2] => {
+            if h1 < h2 {
+                h1 <= hour && hour < h2
+            } else {
+                hour >= h1 || hour < h2
+            }
+        }
+        [h1, m1, h2, m2] => in_range(h1 * 60 + m1, h2 * 60 + m2, hour * 60 + minute),
+        [h1, m1, s1, h2, m2, s2] => in_range(
+            h1 * 3600 + m1 * 60 + s1,
+            h2 * 3600 + m2 * 60 + s2,
+            hour * 3600 + minute * 60 + second,
+        ),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateField {
+    Day(u32),
+    Month(u32),
+    Year(i32),
+}
+
+fn date_field(arg: &PacArg) -> Option<DateField> {
+    match arg {
+        PacArg::Number(n) if (1..=31).contains(n) => Some(DateField::Day(*n as u32)),
+        PacArg::Number(n) if *n > 31 => Some(DateField::Year(*n as i32)),
+        PacArg::Number(_) => None,
+        PacArg::Name(name) => MONTHS
+            .iter()
+            .position(|m| m == name)
+            .map(|i| DateField::Month(i as u32 + 1)),
+    }
+}
+
+// Comparable (year, month, day) key, absent fields are ignored
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+struct DateKey {
+    year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+}
+
+impl DateKey {
+    fn from_fields(fields: &[DateField]) -> Option<DateKey> {
+        let mut key = DateKey::default();
+        for field in fields {
+            match field {
+                DateField::Day(d) if key.day.is_none() => key.day = Some(*d),
+                DateField::Month(m) if key.month.is_none() => key.month = Some(*m),
+                DateField::Year(y) if key.year.is_none() => key.year = Some(*y),
+                _ => return None, // same field given twice
+            }
+        }
+        // A day and a year without month does not define a range
+        if key.day.is_some() && key.year.is_some() && key.month.is_none() {
+            return None;
+        }
+        Some(key)
+    }
+
+    fn same_shape(&self, other: &DateKey) -> bool {
+        self.year.is_some() == other.year.is_some()
+            && self.month.is_some() == other.month.is_some()
+            && self.day.is_some() == other.day.is_some()
+    }
+
+    // Current date reduced to the fields present in this key
+    fn project(&self, now: NaiveDateTime) -> DateKey {
+        DateKey {
+            year: self.year.map(|_| now.year()),
+            month: self.month.map(|_| now.month()),
+            day: self.day.map(|_| now.day()),
+        }
+    }
+}
+
+// dateRange with one field, or two halves giving the start and end of the
+// range: days, months, years, day and month, month and year, or a full date.
+// Ranges without year wrap around when start is after end, ranges with a year
+// are empty then. "GMT" argument is already removed.
+pub fn date_range(args: &[PacArg], now: NaiveDateTime) -> bool {
+    let Some(fields) = args.iter().map(date_field).collect::<Option<Vec<_>>>() else {
+        return false;
+    };
+
+    match fields.len() {
+        1 => match fields[0] {
+            DateField::Day(d) => now.day() == d,
+            DateField::Month(m) => now.month() == m,
+            DateField::Year(y) => now.year() == y,
+        },
+        2 | 4 | 6 => {
+            let (first, second) = fields.split_at(fields.len() / 2);
+            match (DateKey::from_fields(first), DateKey::from_fields(second)) {
+                (Some(start), Some(end)) if start.same_shape(&end) => {
+                    let current = start.project(now);
+                    if start.year.is_some() {
+                        start <= current && current <= end
+                    } else {
+                        in_range(start, end, current)
+                    }
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn n(number: i64) -> PacArg {
+        PacArg::Number(number)
+    }
+
+    fn s(name: &str) -> PacArg {
+        PacArg::Name(name.to_string())
+    }
+
+    // Monday 19 October 2026, 09:30:15
+    fn monday_morning() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(9, 30, 15)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_gmt() {
+        assert_eq!(split_gmt(&[s("MON"), s("GMT")]), (&[s("MON")][..], true));
+        assert_eq!(split_gmt(&[s("MON")]), (&[s("MON")][..], false));
+        assert_eq!(split_gmt(&[]), (&[][..], false));
+    }
+
+    #[test]
+    fn test_weekday_range() {
+        let cases: Vec<(Vec<PacArg>, bool)> = vec![
+            (vec![s("MON")], true),
+            (vec![s("TUE")], false),
+            (vec![s("MON"), s("FRI")], true),
+            (vec![s("TUE"), s("FRI")], false),
+            (vec![s("SAT"), s("MON")], true),
+            (vec![s("FRI"), s("SUN")], false),
+            (vec![s("SUN"), s("SAT")], true),
+            (vec![], false),
+            (vec![s("MON"), s("TUE"), s("WED")], false),
+            (vec![s("MONDAY")], false),
+            (vec![n(1)], false),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(
+                weekday_range(&args, monday_morning()),
+                expected,
+                "{:?}",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_time_range() {
+        let cases: Vec<(Vec<PacArg>, bool)> = vec![
+            // single hour
+            (vec![n(9)], true),
+            (vec![n(10)], false),
+            // hour range, upper hour excluded
+            (vec![n(9), n(17)], true),
+            (vec![n(8), n(9)], false),
+            (vec![n(9), n(10)], true),
+            (vec![n(9), n(9)], true),
+            (vec![n(10), n(17)], false),
+            (vec![n(22), n(10)], true),
+            (vec![n(22), n(9)], false),
+            // hour and minute range, inclusive
+            (vec![n(9), n(30), n(17), n(0)], true),
+            (vec![n(9), n(31), n(17), n(0)], false),
+            (vec![n(8), n(0), n(9), n(30)], true),
+            (vec![n(8), n(0), n(9), n(29)], false),
+            (vec![n(23), n(0), n(9), n(30)], true),
+            // hour, minute and second range, inclusive
+            (vec![n(9), n(30), n(15), n(9), n(30), n(15)], true),
+            (vec![n(9), n(30), n(0), n(9), n(30), n(14)], false),
+            (vec![n(9), n(30), n(16), n(9), n(30), n(14)], false),
+            (vec![n(9), n(30), n(16), n(9), n(30), n(15)], true),
+            // invalid shapes and values
+            (vec![], false),
+            (vec![n(9), n(0), n(17)], false),
+            (vec![n(9), n(0), n(0), n(17), n(0)], false),
+            (vec![n(24)], false),
+            (vec![n(9), n(60), n(17), n(0)], false),
+            (vec![n(-1), n(10)], false),
+            (vec![s("NINE")], false),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(time_range(&args, monday_morning()), expected, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_time_range_upper_hour() {
+        // 17:00:00 is after timeRange(9, 17), and 17:00 inside the minute range
+        let five_pm = NaiveDate::from_ymd_opt(2026, 10, 19)
+            .unwrap()
+            .and_hms_opt(17, 0, 0)
+            .unwrap();
+        assert!(!time_range(&[n(9), n(17)], five_pm));
+        assert!(!time_range(&[n(22), n(17)], five_pm));
+        assert!(time_range(&[n(17), n(18)], five_pm));
+        assert!(time_range(&[n(9), n(0), n(17), n(0)], five_pm));
+    }
+
+    #[test]
+    fn test_date_range() {
+        let cases: Vec<(Vec<PacArg>, bool)> = vec![
+            // single day, month or year
+            (vec![n(19)], true),
+            (vec![n(20)], false),
+            (vec![s("OCT")], true),
+            (vec![s("NOV")], false),
+            (vec![n(2026)], true),
+            (vec![n(2025)], false),
+            // day range
+            (vec![n(1), n(19)], true),
+            (vec![n(20), n(31)], false),
+            (vec![n(25), n(19)], true),
+            (vec![n(25), n(18)], false),
+            // month range
+            (vec![s("SEP"), s("NOV")], true),
+            (vec![s("JAN"), s("SEP")], false),
+            (vec![s("NOV"), s("OCT")], true),
+            // year range
+            (vec![n(2020), n(2030)], true),
+            (vec![n(2027), n(2030)], false),
+            // day and month range
+            (vec![n(1), s("OCT"), n(19), s("OCT")], true),
+            (vec![n(20), s("OCT"), n(1), s("NOV")], false),
+            (vec![n(1), s("DEC"), n(31), s("OCT")], true),
+            (vec![n(1), s("DEC"), n(18), s("OCT")], false),
+            (vec![n(1), s("OCT"), s("NOV"), n(20)], true),
+            // month and year range
+            (vec![s("JUN"), n(2026), s("AUG"), n(2027)], true),
+            (vec![s("NOV"), n(2026), s("AUG"), n(2027)], false),
+            (vec![s("OCT"), n(2025), s("OCT"), n(2026)], true),
+            (vec![s("JAN"), n(2026), s("JAN"), n(2025)], false),
+            // full date range
+            (
+                vec![n(1), s("JAN"), n(2026), n(19), s("OCT"), n(2026)],
+                true,
+            ),
+            (
+                vec![n(20), s("OCT"), n(2026), n(1), s("JAN"), n(2027)],
+                false,
+            ),
+            (
+                vec![n(1), s("OCT"), n(2025), n(31), s("DEC"), n(2027)],
+                true,
+            ),
+            // start after end does not wrap with a year
+            (
+                vec![n(1), s("JAN"), n(2026), n(1), s("JAN"), n(2025)],
+                false,
+            ),
+            (
+                vec![n(1), s("JAN"), n(2026), n(31), s("DEC"), n(2025)],
+                false,
+            ),
+            // invalid shapes and values
+            (vec![], false),
+            (vec![n(1), s("OCT"), n(2026)], false),
+            (vec![n(1), s("OCT"), s("NOV")], false),
+            (vec![n(1), n(2026), n(31), n(2026)], false),
+            (vec![n(1), s("OCT"), n(2026), n(20)], false),
+            (vec![n(0)], false),
+            (vec![s("OCTOBER")], false),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(date_range(&args, monday_morning()), expected, "{:?}", args);
+        }
+    }
+}
@@ -1,4 +1,9 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
 use std::io;
+use std::path::PathBuf;
+use std::process::Command;
 use std::{error, fmt};
 
 use crate::proxyjs::ProxySettings;
@@ -24,12 +29,475 @@ impl error::Error for ProxySettingsError {
     }
 }
 
+// Proxy configuration of one desktop
+#[derive(Debug, PartialEq)]
+pub enum DesktopProxy {
+    // Desktop has no proxy configuration, another desktop may have one
+    Absent,
+    // No proxy, or proxy left to environment variables
+    Environment,
+    Configured(ProxySettings),
+}
+
+type SettingsSource = fn() -> Result<DesktopProxy, ProxySettingsError>;
+
+// Values of an INI-like file, by section and key
+type KeyFile = HashMap<(String, String), String>;
+
+fn parse_keyfile(content: &str) -> KeyFile {
+    let mut values = KeyFile::new();
+    let mut section = String::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = name.to_string();
+        } else if let Some((key, value)) = line.split_once('=') {
+            values.insert(
+                (section.clone(), key.trim().to_string()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    values
+}
+
+fn keyfile_value<'a>(values: &'a KeyFile, section: &str, key: &str) -> Option<&'a str> {
+    values
+        .get(&(section.to_string(), key.to_string()))
+        .map(String::as_str)
+}
+
+// KDE proxy address, "http://proxy.corp 8080" or "proxy.corp:8080"
+fn kde_proxy_address(value: &str) -> Option<String> {
+    let mut fields = value.split_whitespace();
+    let address = fields.next()?;
+    let address = match address.split_once("://") {
+        Some((_, address)) => address,
+        None => address,
+    };
+    let address = address.trim_end_matches('/');
+    if address.is_empty() {
+        return None;
+    }
+    match fields.next().and_then(|port| port.parse::<u16>().ok()) {
+        Some(port) if port != 0 => Some(format!("{}:{}", address, port)),
+        _ => Some(address.to_string()),
+    }
+}
+
+// Proxy server in Windows ProxyServer format, "http=proxy1:8080;socks=proxy2:1080"
+fn per_scheme_proxy_server(addresses: Vec<(&str, Option<String>)>) -> Option<String> {
+    let entries: Vec<String> = addresses
+        .into_iter()
+        .filter_map(|(scheme, address)| Some(format!("{}={}", scheme, address?)))
+        .collect();
+    if entries.is_empty() {
+        None
+    } else {
+        Some(entries.join(";"))
+    }
+}
+
+// Proxy settings from KDE "kioslaverc" content, absent without ProxyType.
+// ProxyType is 0 for no proxy, 1 for manual proxy, 2 for PAC script and 3
+// for WPAD. System environment (4) is left to environment variables resolver.
+pub fn parse_kioslaverc(content: &str) -> DesktopProxy {
+    let values = parse_keyfile(content);
+    let value = |key: &str| keyfile_value(&values, "Proxy Settings", key);
+
+    let Some(proxy_type) = value("ProxyType") else {
+        return DesktopProxy::Absent;
+    };
+    // With reversed exceptions, proxy is used only for listed hosts, bypass
+    // rules cannot express it: settings are left to environment variables
+    if value("ReversedException") == Some("true") {
+        return DesktopProxy::Environment;
+    }
+    let proxy_override = value("NoProxyFor")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let settings = match proxy_type {
+        "1" => ProxySettings {
+            auto_config_url: None,
+            auto_detect: false,
+            proxy_enable: true,
+            proxy_server: per_scheme_proxy_server(
+                [
+                    ("http", "httpProxy"),
+                    ("https", "httpsProxy"),
+                    ("ftp", "ftpProxy"),
+                    ("socks", "socksProxy"),
+                ]
+                .into_iter()
+                .map(|(scheme, key)| (scheme, value(key).and_then(kde_proxy_address)))
+                .collect(),
+            ),
+            proxy_override,
+        },
+        "2" => match value("Proxy Config Script").filter(|url| !url.is_empty()) {
+            Some(url) => ProxySettings {
+                auto_config_url: Some(url.to_string()),
+                auto_detect: false,
+                proxy_enable: false,
+                proxy_server: None,
+                proxy_override,
+            },
+            None => return DesktopProxy::Environment,
+        },
+        "3" => ProxySettings {
+            auto_detect: true,
+            proxy_override,
+            ..Default::default()
+        },
+        _ => return DesktopProxy::Environment,
+    };
+    DesktopProxy::Configured(settings)
+}
+
+// GVariant string, 'text' with backslash escapes
+fn gvariant_string(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut s = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => s.push(chars.next()?),
+            c => s.push(c),
+        }
+    }
+    Some(s)
+}
+
+// GVariant string array, ['a', 'b'] or @as []
+fn gvariant_strings(value: &str) -> Option<Vec<String>> {
+    let value = value.strip_prefix("@as").unwrap_or(value).trim();
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?;
+
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(gvariant_string(item.trim())?);
+                item.clear();
+                continue;
+            }
+            _ => {}
+        }
+        item.push(c);
+    }
+    if !item.trim().is_empty() {
+        items.push(gvariant_string(item.trim())?);
+    }
+    Some(items)
+}
+
+// GVariant integer, with optional type, "8080" or "uint32 8080"
+fn gvariant_integer(value: &str) -> Option<u16> {
+    value.split_whitespace().last()?.parse().ok()
+}
+
+// Proxy settings from "dconf dump /system/proxy/" output, the GNOME
+// org.gnome.system.proxy schema. Unset keys have schema default values, but
+// without mode, proxy is not configured.
+pub fn parse_gnome_proxy_dump(content: &str) -> DesktopProxy {
+    let values = parse_keyfile(content);
+    let string = |section: &str, key: &str| {
+        keyfile_value(&values, section, key)
+            .and_then(gvariant_string)
+            .filter(|s| !s.is_empty())
+    };
+
+    let proxy_override = match keyfile_value(&values, "/", "ignore-hosts") {
+        Some(hosts) => gvariant_strings(hosts).unwrap_or_default(),
+        None => vec!["localhost".into(), "127.0.0.0/8".into(), "::1".into()],
+    };
+
+    let Some(mode) = string("/", "mode") else {
+        return DesktopProxy::Absent;
+    };
+    let settings = match mode.as_str() {
+        "manual" => {
+            // Port 0 is unset in GNOME schema
+            let address = |scheme: &str| {
+                let host = string(scheme, "host")?;
+                match keyfile_value(&values, scheme, "port").and_then(gvariant_integer) {
+                    Some(port) if port != 0 => Some(format!("{}:{}", host, port)),
+                    _ => Some(host),
+                }
+            };
+            ProxySettings {
+                auto_config_url: None,
+                auto_detect: false,
+                proxy_enable: true,
+                proxy_server: per_scheme_proxy_server(
+                    ["http", "https", "ftp", "socks"]
+                        .into_iter()
+                        .map(|scheme| (scheme, address(scheme)))
+                        .collect(),
+                ),
+                proxy_override,
+            }
+        }
+        // Without PAC script URL, GNOME uses WPAD
+        "auto" => ProxySettings {
+            auto_detect: string("/", "autoconfig-url").is_none(),
+            auto_config_url: string("/", "autoconfig-url"),
+            proxy_enable: false,
+            proxy_server: None,
+            proxy_override,
+        },
+        _ => return DesktopProxy::Environment,
+    };
+    DesktopProxy::Configured(settings)
+}
+
+fn gnome_settings() -> Result<DesktopProxy, ProxySettingsError> {
+    // Without dconf, GNOME is not installed
+    let Ok(output) = Command::new("dconf")
+        .args(["dump", "/system/proxy/"])
+        .output()
+    else {
+        return Ok(DesktopProxy::Absent);
+    };
+    if !output.status.success() {
+        return Ok(DesktopProxy::Absent);
+    }
+    Ok(parse_gnome_proxy_dump(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+fn kde_settings() -> Result<DesktopProxy, ProxySettingsError> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => return Ok(DesktopProxy::Absent),
+        },
+    };
+    let path = config_home.join("kioslaverc");
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(parse_kioslaverc(&content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(DesktopProxy::Absent),
+        Err(e) => Err(ProxySettingsError {
+            message: format!("KDE proxy settings {}", path.display()),
+            source: e,
+        }),
+    }
+}
+
+// Desktop proxy settings, KDE first on a KDE desktop, GNOME otherwise. First
+// desktop with a proxy configuration decides, even for no proxy.
 #[cfg(unix)]
 pub fn get_proxy_settings() -> Result<ProxySettings, ProxySettingsError> {
-    Ok(ProxySettings {
-        auto_config_url: None,
-        proxy_enable: false,
-        proxy_server: None,
-        proxy_override: vec![],
-    })
+    let kde = env::var("XDG_CURRENT_DESKTOP")
+        .is_ok_and(|desktop| desktop.to_ascii_uppercase().contains("KDE"));
+    let sources: [SettingsSource; 2] = if kde {
+        [kde_settings, gnome_settings]
+    } else {
+        [gnome_settings, kde_settings]
+    };
+    desktop_settings(&sources)
+}
+
+fn desktop_settings(sources: &[SettingsSource]) -> Result<ProxySettings, ProxySettingsError> {
+    for source in sources {
+        match source()? {
+            DesktopProxy::Absent => continue,
+            DesktopProxy::Environment => break,
+            DesktopProxy::Configured(settings) => return Ok(settings),
+        }
+    }
+    Ok(ProxySettings::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kioslaverc_manual() {
+        let settings = parse_kioslaverc(include_str!("../../tests/fixtures/kioslaverc-manual"));
+        assert_eq!(
+            settings,
+            DesktopProxy::Configured(ProxySettings {
+                auto_config_url: None,
+                auto_detect: false,
+                proxy_enable: true,
+                proxy_server: Some(
+                    "http=proxy.corp:8080;https=secure.corp:8443;ftp=ftp.corp:2121;socks=socks.corp:1080"
+                        .to_string()
+                ),
+                proxy_override: vec![
+                    "localhost".to_string(),
+                    "127.0.0.1".to_string(),
+                    ".corp".to_string()
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_kioslaverc_pac() {
+        let settings = parse_kioslaverc(include_str!("../../tests/fixtures/kioslaverc-pac"));
+        assert_eq!(
+            settings,
+            DesktopProxy::Configured(ProxySettings {
+                auto_config_url: Some("http://wpad.corp/proxy.pac".to_string()),
+                auto_detect: false,
+                proxy_enable: false,
+                proxy_server: None,
+                proxy_override: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_kioslaverc_not_configured() {
+        assert_eq!(parse_kioslaverc(""), DesktopProxy::Absent);
+        assert_eq!(
+            parse_kioslaverc("[Proxy Settings]\nNoProxyFor=.corp\n"),
+            DesktopProxy::Absent
+        );
+        assert_eq!(
+            parse_kioslaverc("[Proxy Settings]\nProxyType=0\n"),
+            DesktopProxy::Environment
+        );
+        assert_eq!(
+            parse_kioslaverc("[Proxy Settings]\nProxyType=4\n"),
+            DesktopProxy::Environment
+        );
+        // PAC script mode without script
+        assert_eq!(
+            parse_kioslaverc("[Proxy Settings]\nProxyType=2\nProxy Config Script=\n"),
+            DesktopProxy::Environment
+        );
+    }
+
+    #[test]
+    fn test_kioslaverc_reversed_exceptions() {
+        // Proxy only for listed hosts, left to environment variables
+        let content = "[Proxy Settings]
+NoProxyFor=.corp,intranet
+ProxyType=1
+ReversedException=true
+httpProxy=http://proxy.corp 8080
+";
+        assert_eq!(parse_kioslaverc(content), DesktopProxy::Environment);
+        assert!(matches!(
+            parse_kioslaverc(&content.replace("=true", "=false")),
+            DesktopProxy::Configured(_)
+        ));
+    }
+
+    #[test]
+    fn test_gnome_manual() {
+        let settings =
+            parse_gnome_proxy_dump(include_str!("../../tests/fixtures/dconf-proxy-manual"));
+        assert_eq!(
+            settings,
+            DesktopProxy::Configured(ProxySettings {
+                auto_config_url: None,
+                auto_detect: false,
+                proxy_enable: true,
+                proxy_server: Some(
+                    "http=proxy.corp:8080;https=secure.corp:8443;ftp=ftp.corp:2121;socks=socks.corp:1080"
+                        .to_string()
+                ),
+                proxy_override: vec![
+                    "localhost".to_string(),
+                    "127.0.0.0/8".to_string(),
+                    "*.corp".to_string()
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_gnome_auto() {
+        let settings =
+            parse_gnome_proxy_dump(include_str!("../../tests/fixtures/dconf-proxy-auto"));
+        assert_eq!(
+            settings,
+            DesktopProxy::Configured(ProxySettings {
+                auto_config_url: Some("http://wpad.corp/proxy.pac".to_string()),
+                auto_detect: false,
+                proxy_enable: false,
+                proxy_server: None,
+                proxy_override: vec![
+                    "localhost".to_string(),
+                    "127.0.0.0/8".to_string(),
+                    "::1".to_string()
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_auto_detect() {
+        for proxy in [
+            parse_kioslaverc("[Proxy Settings]\nProxyType=3\n"),
+            parse_gnome_proxy_dump("[/]\nmode='auto'\n"),
+        ] {
+            let DesktopProxy::Configured(settings) = proxy else {
+                panic!("{:?}", proxy);
+            };
+            assert!(settings.auto_detect && settings.auto_config_url.is_none());
+        }
+    }
+
+    #[test]
+    fn test_gnome_not_configured() {
+        assert_eq!(parse_gnome_proxy_dump(""), DesktopProxy::Absent);
+        assert_eq!(
+            parse_gnome_proxy_dump("[/]\nmode='none'\n"),
+            DesktopProxy::Environment
+        );
+    }
+
+    #[test]
+    fn test_first_configured_desktop_decides() {
+        let kde_none: SettingsSource = || Ok(parse_kioslaverc("[Proxy Settings]\nProxyType=0\n"));
+        let kde_absent: SettingsSource = || Ok(parse_kioslaverc(""));
+        let gnome_manual: SettingsSource = || {
+            Ok(parse_gnome_proxy_dump(include_str!(
+                "../../tests/fixtures/dconf-proxy-manual"
+            )))
+        };
+        // KDE without proxy, stale GNOME manual proxy is ignored
+        assert_eq!(
+            desktop_settings(&[kde_none, gnome_manual]).unwrap(),
+            ProxySettings::default()
+        );
+        assert!(
+            desktop_settings(&[kde_absent, gnome_manual])
+                .unwrap()
+                .proxy_enable
+        );
+    }
+
+    #[test]
+    fn test_gvariant_values() {
+        assert_eq!(gvariant_string(r"'it\'s'"), Some("it's".to_string()));
+        assert_eq!(gvariant_strings("@as []"), Some(vec![]));
+        assert_eq!(
+            gvariant_strings(r"['a, b', 'c']"),
+            Some(vec!["a, b".to_string(), "c".to_string()])
+        );
+        assert_eq!(gvariant_integer("uint32 3128"), Some(3128));
+    }
 }
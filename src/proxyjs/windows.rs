@@ -43,6 +43,7 @@ pub fn get_proxy_settings() -> Result<ProxySettings, ProxySettingsError> {
         })?;
 
     let auto_config_url = settings.get_value("AutoConfigURL").ok();
+    let auto_detect = settings.get_value::<u32, _>("AutoDetect").unwrap_or(0) != 0;
     let proxy_server = settings.get_value("ProxyServer").ok();
     let proxy_enable = settings.get_value::<u32, _>("ProxyEnable").unwrap_or(0) != 0;
     let proxy_override_string = settings.get_value::<String, _>("ProxyOverride").ok();
@@ -59,6 +60,7 @@ pub fn get_proxy_settings() -> Result<ProxySettings, ProxySettingsError> {
 
     Ok(ProxySettings {
         auto_config_url,
+        auto_detect,
         proxy_enable,
         proxy_server,
         proxy_override,
@@ -0,0 +1,961 @@
+use rquickjs::{Context, Runtime};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use url::Url;
+
+use crate::bypass::{BypassRule, find_bypass};
+use crate::envproxy::EnvResolver;
+use crate::error::{ResolveError, engine_error, script_error};
+use crate::memo::{CacheStats, Memo};
+use crate::pacloader::PacLoader;
+use crate::parser::{ProxyDirective, ProxyParser, format_directives};
+use crate::proxyjs::{
+    PacEnvironment, ProxySettings, ProxySettingsError, bind_pac_methods, get_proxy_settings,
+    lock_down, pac_context,
+};
+use crate::wpad::{Wpad, search_domain};
+
+pub(crate) fn parse_url(url: &str) -> Result<Url, ResolveError> {
+    Url::parse(url).map_err(|source| ResolveError::InvalidUrl {
+        url: url.to_string(),
+        source,
+    })
+}
+
+/// Source of proxy answers, in PAC format like "PROXY proxy.corp:8080; DIRECT".
+///
+/// Implement it to plug another source in [`Resolver::new`].
+pub trait ProxyResolver {
+    /// Raw answer for URL, validated by [`Resolver`].
+    fn resolve(&self, url: &str) -> Result<String, ResolveError>;
+
+    /// Bypass rules, in canonical form.
+    fn no_proxy(&self) -> Vec<String>;
+
+    /// Resolver name in reports, like "pac" or "static".
+    fn kind(&self) -> &'static str;
+
+    /// Location of PAC script, if any.
+    fn pac_source(&self) -> Option<String> {
+        None
+    }
+
+    /// Bypass pattern matching URL, if any.
+    fn bypass_match(&self, _url: &str) -> Option<String> {
+        None
+    }
+
+    /// Hits and misses of answer cache, if any.
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+}
+
+/// Proxy resolver with validated answers, see [`ResolverBuilder`].
+pub struct Resolver {
+    inner: Box<dyn ProxyResolver>,
+}
+
+impl Resolver {
+    pub fn new(resolver: Box<dyn ProxyResolver>) -> Self {
+        Resolver { inner: resolver }
+    }
+
+    /// Proxy directives for URL, in order of preference.
+    pub fn resolve(&self, url: &str) -> Result<Vec<ProxyDirective>, ResolveError> {
+        let answer = self.inner.resolve(url)?;
+        let mut parser = ProxyParser::new(&answer);
+        parser.parse().map_err(|error| ResolveError::InvalidAnswer {
+            position: parser.position(),
+            answer: answer.clone(),
+            error,
+        })
+    }
+
+    /// Proxy for URL in PAC format, like "PROXY proxy.corp:8080; DIRECT".
+    pub fn find_proxy(&self, url: &str) -> Result<String, ResolveError> {
+        Ok(format_directives(&self.resolve(url)?))
+    }
+
+    /// Bypass rules, in canonical form.
+    pub fn no_proxy(&self) -> Vec<String> {
+        self.inner.no_proxy()
+    }
+
+    /// Resolver name: "pac", "static", "env" or "direct".
+    pub fn kind(&self) -> &'static str {
+        self.inner.kind()
+    }
+
+    /// Location of PAC script, if any.
+    pub fn pac_source(&self) -> Option<String> {
+        self.inner.pac_source()
+    }
+
+    /// Bypass rule matching URL, if any.
+    pub fn bypass_match(&self, url: &str) -> Option<String> {
+        self.inner.bypass_match(url)
+    }
+
+    /// Hits and misses of PAC answer cache, if enabled.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+}
+
+// Proxy for URL scheme, from a single "proxy:8080" address, or per scheme
+// addresses in Windows ProxyServer format, like
+// "http=proxy1:8080;https=proxy2:8443;socks=proxy3:1080". Without an entry
+// for the scheme, the unqualified entry is used, then the socks one.
+pub(crate) fn select_static_proxy(proxy_server: &str, scheme: &str) -> String {
+    let scheme = match scheme {
+        "ws" => "http",
+        "wss" => "https",
+        scheme => scheme,
+    };
+    let mut unqualified = None;
+    let mut socks = None;
+    for entry in proxy_server.split([';', ' ']).map(str::trim) {
+        let (protocol, address) = match entry.split_once('=') {
+            Some((protocol, address)) => (Some(protocol.trim()), address.trim()),
+            None => (None, entry),
+        };
+        // Address may have a scheme, "http=http://proxy1:8080"
+        let address = match address.split_once("://") {
+            Some((_, address)) => address.trim_end_matches('/'),
+            None => address,
+        };
+        if address.is_empty() {
+            continue;
+        }
+        match protocol {
+            Some(protocol) if protocol.eq_ignore_ascii_case(scheme) => {
+                return format!("PROXY {}", address);
+            }
+            Some(protocol) if protocol.eq_ignore_ascii_case("socks") => {
+                socks.get_or_insert(address);
+            }
+            Some(_) => {}
+            None => {
+                unqualified.get_or_insert(address);
+            }
+        }
+    }
+    match (unqualified, socks) {
+        (Some(address), _) => format!("PROXY {}", address),
+        (None, Some(address)) => format!("SOCKS {}", address),
+        (None, None) => "DIRECT".to_string(),
+    }
+}
+
+/// Static proxy, like "proxy:8080" or "http=proxy1:8080;https=proxy2:8443".
+pub struct StaticResolver {
+    pub(crate) proxy_server: String,
+    pub(crate) bypass: Vec<BypassRule>,
+}
+
+impl StaticResolver {
+    pub fn new(proxy_server: &str, bypass: Vec<BypassRule>) -> Self {
+        StaticResolver {
+            proxy_server: proxy_server.to_string(),
+            bypass,
+        }
+    }
+}
+
+impl ProxyResolver for StaticResolver {
+    fn resolve(&self, url: &str) -> Result<String, ResolveError> {
+        let parsed = parse_url(url)?;
+
+        if find_bypass(&self.bypass, &parsed).is_some() {
+            Ok("DIRECT".to_string())
+        } else {
+            Ok(select_static_proxy(&self.proxy_server, parsed.scheme()))
+        }
+    }
+
+    fn no_proxy(&self) -> Vec<String> {
+        self.bypass.iter().map(BypassRule::to_string).collect()
+    }
+
+    fn kind(&self) -> &'static str {
+        "static"
+    }
+
+    fn bypass_match(&self, url: &str) -> Option<String> {
+        find_bypass(&self.bypass, &Url::parse(url).ok()?)
+    }
+}
+
+/// Limits of PAC script evaluation, against endless loops and runaway
+/// allocations. `None` disables a limit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScriptLimits {
+    /// Maximum duration of script loading, and of each FindProxyForURL call
+    pub timeout: Option<Duration>,
+    /// Maximum memory of JavaScript runtime, in bytes
+    pub memory_limit: Option<usize>,
+    /// Restrict globals to ECMAScript 5 built-ins and PAC functions
+    pub strict: bool,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        ScriptLimits {
+            timeout: Some(Duration::from_secs(5)),
+            memory_limit: Some(64 * 1024 * 1024),
+            strict: false,
+        }
+    }
+}
+
+// Interrupts script evaluation running past its deadline
+#[derive(Clone, Default)]
+struct Deadline {
+    timeout: Option<Duration>,
+    at: Rc<Cell<Option<Instant>>>,
+    expired: Rc<Cell<bool>>,
+}
+
+impl Deadline {
+    fn new(rt: &Runtime, timeout: Option<Duration>) -> Self {
+        let deadline = Deadline {
+            timeout,
+            ..Default::default()
+        };
+        if timeout.is_some() {
+            let handler = deadline.clone();
+            rt.set_interrupt_handler(Some(Box::new(move || handler.check())));
+        }
+        deadline
+    }
+
+    // Called by QuickJS while running, true interrupts script
+    fn check(&self) -> bool {
+        match self.at.get() {
+            Some(at) if Instant::now() >= at => {
+                self.expired.set(true);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Run evaluation within timeout, interrupted evaluation is a timeout error
+    fn run<T>(&self, eval: impl FnOnce() -> Result<T, ResolveError>) -> Result<T, ResolveError> {
+        self.at
+            .set(self.timeout.map(|timeout| Instant::now() + timeout));
+        self.expired.set(false);
+        let result = eval();
+        self.at.set(None);
+        match (result, self.timeout) {
+            (Err(_), Some(limit)) if self.expired.get() => Err(ResolveError::Timeout { limit }),
+            (result, _) => result,
+        }
+    }
+}
+
+/// Key of cached PAC answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacCacheKey {
+    /// Scheme, host and port, for scripts ignoring URL path and query
+    Host,
+    /// Complete URL, for scripts depending on URL path or query
+    Url,
+    /// No cache, FindProxyForURL is called for each URL
+    Off,
+}
+
+/// PAC script evaluated with QuickJS.
+pub struct PACResolver {
+    ctx: Context,
+    deadline: Deadline,
+    source: String,
+    bypass: Vec<BypassRule>,
+    env: Rc<PacEnvironment>,
+    cache_key: PacCacheKey,
+    answers: Memo<String>,
+    // Environment generation of cached answers
+    generation: Cell<u64>,
+}
+
+impl PACResolver {
+    /// Evaluate PAC script with default [`ScriptLimits`], `source` is its
+    /// location in reports.
+    pub fn from_script(
+        source: &str,
+        pac_script: String,
+        bypass: Vec<BypassRule>,
+        env: Rc<PacEnvironment>,
+        trace: bool,
+    ) -> Result<Self, ResolveError> {
+        Self::with_limits(
+            source,
+            pac_script,
+            bypass,
+            env,
+            trace,
+            ScriptLimits::default(),
+        )
+    }
+
+    /// Evaluate PAC script within `limits`, failing on script errors or when
+    /// the script engine cannot be created.
+    pub fn with_limits(
+        source: &str,
+        pac_script: String,
+        bypass: Vec<BypassRule>,
+        env: Rc<PacEnvironment>,
+        trace: bool,
+        limits: ScriptLimits,
+    ) -> Result<Self, ResolveError> {
+        let rt = Runtime::new().map_err(engine_error)?;
+        if let Some(memory_limit) = limits.memory_limit {
+            rt.set_memory_limit(memory_limit);
+        }
+        let deadline = Deadline::new(&rt, limits.timeout);
+        let context = pac_context(&rt).map_err(engine_error)?;
+
+        deadline.run(|| {
+            context.with(|ctx| {
+                // Parse PAC source code
+                let globals = ctx.globals();
+                bind_pac_methods(&globals, env.clone(), trace);
+                lock_down(&ctx, limits.strict).map_err(|error| script_error(&ctx, error))?;
+                ctx.eval::<(), _>(pac_script)
+                    .map_err(|error| script_error(&ctx, error))
+            })
+        })?;
+        Ok(PACResolver {
+            ctx: context,
+            deadline,
+            source: source.to_string(),
+            bypass,
+            env,
+            cache_key: PacCacheKey::Off,
+            answers: Memo::default(),
+            generation: Cell::new(0),
+        })
+    }
+
+    /// Keep answers by key for ttl. Answers are computed again when PAC
+    /// environment changes, like with `set_time`.
+    pub fn cache(mut self, key: PacCacheKey, ttl: Duration) -> Self {
+        self.cache_key = key;
+        self.answers = Memo::new(if key == PacCacheKey::Off {
+            Duration::ZERO
+        } else {
+            ttl
+        });
+        self
+    }
+
+    // Run f in script context, to inspect globals
+    #[cfg(feature = "cli")]
+    pub(crate) fn with_context<R>(&self, f: impl for<'js> FnOnce(rquickjs::Ctx<'js>) -> R) -> R {
+        self.ctx.with(f)
+    }
+
+    fn find_proxy_for_url(&self, url: &str, host: &str) -> Result<String, ResolveError> {
+        self.deadline.run(|| {
+            self.ctx.with(|ctx| {
+                let globals = ctx.globals();
+                // Microsoft IPv6 aware entry point has precedence when defined
+                let find_proxy_for_url = ["FindProxyForURLEx", "FindProxyForURL"]
+                    .iter()
+                    .find_map(|name| {
+                        globals
+                            .get::<_, rquickjs::Value>(*name)
+                            .ok()
+                            .and_then(|value| value.into_function())
+                    })
+                    .ok_or(ResolveError::MissingEntryPoint)?;
+
+                let result = find_proxy_for_url
+                    .call((url.to_string(), host.to_string()))
+                    .map_err(|error| script_error(&ctx, error));
+                ctx.run_gc();
+                result
+            })
+        })
+    }
+}
+
+impl ProxyResolver for PACResolver {
+    fn resolve(&self, url: &str) -> Result<String, ResolveError> {
+        let parsed = parse_url(url)?;
+        let host = parsed.host_str().unwrap_or("");
+
+        if find_bypass(&self.bypass, &parsed).is_some() {
+            return Ok("DIRECT".to_string());
+        }
+        let key = match self.cache_key {
+            PacCacheKey::Host => format!(
+                "{}://{}:{}",
+                parsed.scheme(),
+                host,
+                parsed.port_or_known_default().unwrap_or(0)
+            ),
+            PacCacheKey::Url => url.to_string(),
+            PacCacheKey::Off => return self.find_proxy_for_url(url, host),
+        };
+        if self.generation.get() != self.env.generation() {
+            self.generation.set(self.env.generation());
+            self.answers.clear();
+        }
+        self.answers
+            .get_or_try_insert(&key, || self.find_proxy_for_url(url, host))
+    }
+
+    fn no_proxy(&self) -> Vec<String> {
+        self.bypass.iter().map(BypassRule::to_string).collect()
+    }
+
+    fn kind(&self) -> &'static str {
+        "pac"
+    }
+
+    fn pac_source(&self) -> Option<String> {
+        Some(self.source.clone())
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        (self.cache_key != PacCacheKey::Off).then(|| self.answers.stats())
+    }
+
+    fn bypass_match(&self, url: &str) -> Option<String> {
+        find_bypass(&self.bypass, &Url::parse(url).ok()?)
+    }
+}
+
+/// Always "DIRECT".
+pub struct DirectResolver;
+
+impl ProxyResolver for DirectResolver {
+    fn resolve(&self, _url: &str) -> Result<String, ResolveError> {
+        Ok("DIRECT".to_string())
+    }
+
+    fn no_proxy(&self) -> Vec<String> {
+        vec![]
+    }
+
+    fn kind(&self) -> &'static str {
+        "direct"
+    }
+}
+
+/// Build a [`Resolver`] from [`ProxySettings`]: PAC script first, then
+/// static proxy. Without both, proxy environment variables are used on Unix,
+/// and direct access otherwise.
+pub struct ResolverBuilder {
+    settings: ProxySettings,
+    env: Rc<PacEnvironment>,
+    loader: PacLoader,
+    wpad: Option<Wpad>,
+    domain: Option<String>,
+    limits: ScriptLimits,
+    cache_key: PacCacheKey,
+    cache_ttl: Duration,
+    verbose: bool,
+    trace: bool,
+}
+
+impl ResolverBuilder {
+    pub fn new(settings: ProxySettings) -> Self {
+        ResolverBuilder {
+            settings,
+            env: Rc::default(),
+            loader: PacLoader::default(),
+            wpad: None,
+            domain: None,
+            limits: ScriptLimits::default(),
+            cache_key: PacCacheKey::Off,
+            cache_ttl: Duration::ZERO,
+            verbose: false,
+            trace: false,
+        }
+    }
+
+    /// Builder from system settings: Windows Internet Settings, GNOME or KDE.
+    pub fn from_system() -> Result<Self, ProxySettingsError> {
+        Ok(Self::new(get_proxy_settings()?))
+    }
+
+    /// Clock, DNS and local addresses seen by PAC scripts.
+    pub fn environment(mut self, env: Rc<PacEnvironment>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Loader for PAC script, with cache settings.
+    pub fn loader(mut self, loader: PacLoader) -> Self {
+        self.loader = loader;
+        self
+    }
+
+    /// Web Proxy Auto-Discovery used when settings ask for auto-detection,
+    /// with loader of builder by default.
+    pub fn wpad(mut self, wpad: Wpad) -> Self {
+        self.wpad = Some(wpad);
+        self
+    }
+
+    /// Domain searched by auto-detection, instead of host search domain.
+    pub fn domain(mut self, domain: Option<String>) -> Self {
+        self.domain = domain;
+        self
+    }
+
+    /// Time and memory limits of PAC script evaluation.
+    pub fn limits(mut self, limits: ScriptLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Keep PAC answers by key, and system DNS answers, for ttl. Nothing is
+    /// cached with `PacCacheKey::Off`, the default.
+    pub fn cache(mut self, key: PacCacheKey, ttl: Duration) -> Self {
+        self.cache_key = key;
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Print settings on error output.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Trace PAC helper calls on error output.
+    pub fn trace(mut self, trace: bool) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Load PAC script, if any, and build resolver.
+    pub fn build(self) -> Result<Resolver, ResolveError> {
+        let bypass = BypassRule::parse_list(&self.settings.proxy_override);
+        self.env.set_dns_ttl(if self.cache_key == PacCacheKey::Off {
+            Duration::ZERO
+        } else {
+            self.cache_ttl
+        });
+
+        if let Some(pac_url) = &self.settings.auto_config_url {
+            if self.verbose {
+                eprintln!("PAC_URL={}", pac_url);
+            }
+            let pac_script =
+                self.loader
+                    .load(pac_url)
+                    .map_err(|source| ResolveError::PacUnavailable {
+                        location: pac_url.clone(),
+                        source,
+                    })?;
+
+            return Ok(Resolver::new(Box::new(
+                PACResolver::with_limits(
+                    pac_url,
+                    pac_script,
+                    bypass,
+                    self.env,
+                    self.trace,
+                    self.limits,
+                )?
+                .cache(self.cache_key, self.cache_ttl),
+            )));
+        }
+
+        // Without discovered PAC script, other settings are used
+        if self.settings.auto_detect {
+            let wpad = self
+                .wpad
+                .unwrap_or_else(|| Wpad::new(self.loader))
+                .verbose(self.verbose);
+            let domain = self.domain.or_else(search_domain);
+            if self.verbose && domain.is_none() {
+                eprintln!("WPAD: no search domain");
+            }
+            if let Some((pac_url, pac_script)) = domain.and_then(|domain| wpad.discover(&domain)) {
+                if self.verbose {
+                    eprintln!("PAC_URL={}", pac_url);
+                }
+                return Ok(Resolver::new(Box::new(
+                    PACResolver::with_limits(
+                        &pac_url,
+                        pac_script,
+                        bypass,
+                        self.env,
+                        self.trace,
+                        self.limits,
+                    )?
+                    .cache(self.cache_key, self.cache_ttl),
+                )));
+            }
+        }
+
+        if self.settings.proxy_enable {
+            let static_proxy = StaticResolver {
+                proxy_server: self.settings.proxy_server.clone().unwrap_or_default(),
+                bypass,
+            };
+
+            if self.verbose {
+                eprintln!("HTTP_PROXY={}", static_proxy.proxy_server);
+                eprintln!("NO_PROXY={}", static_proxy.no_proxy().join(","));
+            }
+            Ok(Resolver::new(Box::new(static_proxy)))
+        } else {
+            // Without system settings, proxy environment variables are used
+            #[cfg(unix)]
+            if let Some(env_proxy) = EnvResolver::from_env(bypass) {
+                if self.verbose {
+                    for (name, value) in env_proxy.describe() {
+                        eprintln!("{}={}", name, value);
+                    }
+                }
+                return Ok(Resolver::new(Box::new(env_proxy)));
+            }
+
+            if self.verbose {
+                eprintln!("HTTP_PROXY=");
+                eprintln!("NO_PROXY=");
+            }
+            Ok(Resolver::new(Box::new(DirectResolver)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ProxyParseError;
+
+    #[test]
+    fn test_static_proxy_per_scheme() {
+        let proxy_server = "http=proxy1:8080;https=http://proxy2:8443/;socks=proxy3:1080";
+        let cases = [
+            ("http", "PROXY proxy1:8080"),
+            ("ws", "PROXY proxy1:8080"),
+            ("https", "PROXY proxy2:8443"),
+            ("ftp", "SOCKS proxy3:1080"),
+        ];
+        for (scheme, expected) in cases {
+            assert_eq!(select_static_proxy(proxy_server, scheme), expected);
+        }
+        assert_eq!(
+            select_static_proxy("proxy.corp:8080", "https"),
+            "PROXY proxy.corp:8080"
+        );
+        assert_eq!(
+            select_static_proxy("https=secure:8443;proxy.corp:8080", "ftp"),
+            "PROXY proxy.corp:8080"
+        );
+        assert_eq!(select_static_proxy("http=proxy1:8080", "https"), "DIRECT");
+        assert_eq!(select_static_proxy("", "http"), "DIRECT");
+    }
+
+    #[test]
+    fn test_static_resolver_per_scheme() {
+        let resolver = Resolver::new(Box::new(StaticResolver {
+            proxy_server: "http=proxy1:8080; https=proxy2:8443; socks=proxy3:1080".to_string(),
+            bypass: vec![BypassRule::parse("*.corp")],
+        }));
+        assert_eq!(
+            resolver.find_proxy("https://example.com/").unwrap(),
+            "PROXY proxy2:8443"
+        );
+        assert_eq!(
+            resolver.find_proxy("ftp://example.com/").unwrap(),
+            "SOCKS proxy3:1080"
+        );
+        assert_eq!(resolver.find_proxy("http://cvs.corp/").unwrap(), "DIRECT");
+    }
+
+    fn pac_resolver(script: &str) -> Result<Resolver, ResolveError> {
+        let resolver =
+            PACResolver::from_script("test.pac", script.to_string(), vec![], Rc::default(), false)?;
+        Ok(Resolver::new(Box::new(resolver)))
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        let resolver = pac_resolver("function FindProxyForURL(url, host) { return 'DIRECT'; }");
+        let error = resolver.unwrap().resolve("not a url").unwrap_err();
+        assert!(matches!(error, ResolveError::InvalidUrl { .. }));
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_missing_entry_point() {
+        let resolver = pac_resolver("function FindProxy(url, host) { return 'DIRECT'; }");
+        let error = resolver
+            .unwrap()
+            .resolve("http://example.com/")
+            .unwrap_err();
+        assert!(matches!(error, ResolveError::MissingEntryPoint));
+    }
+
+    #[test]
+    fn test_ex_entry_point_first() {
+        let resolver = pac_resolver(
+            "function FindProxyForURL(url, host) { return 'PROXY old.corp:8080'; }
+             function FindProxyForURLEx(url, host) { return 'PROXY ex.corp:8080'; }",
+        );
+        assert_eq!(
+            resolver.unwrap().find_proxy("http://example.com/").unwrap(),
+            "PROXY ex.corp:8080"
+        );
+    }
+
+    #[test]
+    fn test_script_syntax_error() {
+        let error = pac_resolver("function FindProxyForURL(url, host) {")
+            .err()
+            .unwrap();
+        assert!(matches!(error, ResolveError::Script { .. }));
+    }
+
+    #[test]
+    fn test_script_exception() {
+        let resolver = pac_resolver(
+            "function FindProxyForURL(url, host) { throw new Error('no route to ' + host); }",
+        );
+        match resolver.unwrap().resolve("http://example.com/") {
+            Err(ResolveError::Script { message, stack }) => {
+                assert_eq!(message, "no route to example.com");
+                assert!(stack.unwrap().contains("FindProxyForURL"));
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_script_throws_string() {
+        let resolver = pac_resolver("function FindProxyForURL(url, host) { throw 'oops'; }");
+        match resolver.unwrap().resolve("http://example.com/") {
+            Err(ResolveError::Script { message, .. }) => assert_eq!(message, "oops"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_answer() {
+        let resolver =
+            pac_resolver("function FindProxyForURL(url, host) { return 'PROXY proxy:abc'; }");
+        match resolver.unwrap().resolve("http://example.com/") {
+            Err(ResolveError::InvalidAnswer {
+                answer,
+                position,
+                error,
+            }) => {
+                assert_eq!(answer, "PROXY proxy:abc");
+                assert_eq!(position, 12);
+                assert_eq!(error, ProxyParseError::InvalidPort);
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    fn limited_resolver(script: &str, limits: ScriptLimits) -> Result<Resolver, ResolveError> {
+        let resolver = PACResolver::with_limits(
+            "test.pac",
+            script.to_string(),
+            vec![],
+            Rc::default(),
+            false,
+            limits,
+        )?;
+        Ok(Resolver::new(Box::new(resolver)))
+    }
+
+    const SHORT_TIMEOUT: ScriptLimits = ScriptLimits {
+        timeout: Some(Duration::from_millis(100)),
+        memory_limit: None,
+        strict: false,
+    };
+
+    #[test]
+    fn test_endless_loop_in_call() {
+        let resolver = limited_resolver(
+            "function FindProxyForURL(url, host) {
+                if (host == 'loop.corp') while (true) {}
+                return 'DIRECT';
+            }",
+            SHORT_TIMEOUT,
+        )
+        .unwrap();
+        let started = Instant::now();
+        let error = resolver.resolve("http://loop.corp/").unwrap_err();
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(matches!(error, ResolveError::Timeout { .. }));
+        assert_eq!(error.to_string(), "PAC script interrupted after 100 ms");
+        assert_eq!(error.exit_code(), 10);
+
+        // Deadline applies to each call, resolver is still usable
+        assert_eq!(
+            resolver.find_proxy("http://example.com/").unwrap(),
+            "DIRECT"
+        );
+    }
+
+    #[test]
+    fn test_endless_loop_in_script() {
+        let error = limited_resolver(
+            "for (;;) {} function FindProxyForURL(url, host) { return 'DIRECT'; }",
+            SHORT_TIMEOUT,
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(error, ResolveError::Timeout { .. }));
+    }
+
+    #[test]
+    fn test_timeout_not_caught_by_script() {
+        let resolver = limited_resolver(
+            "function FindProxyForURL(url, host) {
+                for (;;) { try { while (true) {} } catch (e) {} }
+            }",
+            SHORT_TIMEOUT,
+        )
+        .unwrap();
+        let error = resolver.resolve("http://example.com/").unwrap_err();
+        assert!(matches!(error, ResolveError::Timeout { .. }));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let limits = ScriptLimits {
+            memory_limit: Some(8 * 1024 * 1024),
+            ..Default::default()
+        };
+        let resolver = limited_resolver(
+            "function FindProxyForURL(url, host) {
+                var chunks = [];
+                while (true) chunks.push(new Array(100000).fill(host));
+            }",
+            limits,
+        )
+        .unwrap();
+        match resolver.resolve("http://example.com/") {
+            Err(ResolveError::Script { message, .. }) => assert_eq!(message, "out of memory"),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_strict_script() {
+        let script = "function FindProxyForURL(url, host) {
+            if (host == 'reflect.corp') return Reflect.apply(String, null, ['PROXY proxy:8080']);
+            return isPlainHostName(host) ? 'DIRECT' : 'PROXY proxy:3128';
+        }";
+        let limits = ScriptLimits {
+            strict: true,
+            ..Default::default()
+        };
+        let resolver = limited_resolver(script, limits).unwrap();
+        assert_eq!(resolver.find_proxy("http://intranet/").unwrap(), "DIRECT");
+        match resolver.resolve("http://reflect.corp/") {
+            Err(ResolveError::Script { message, .. }) => {
+                assert_eq!(message, "Reflect is not available to strict PAC scripts")
+            }
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+
+        let resolver = limited_resolver(script, ScriptLimits::default()).unwrap();
+        assert_eq!(
+            resolver.find_proxy("http://reflect.corp/").unwrap(),
+            "PROXY proxy:8080"
+        );
+    }
+
+    // Answer changes at each FindProxyForURL call
+    const COUNTING_SCRIPT: &str = "var calls = 0;
+        function FindProxyForURL(url, host) { calls++; return 'PROXY proxy:' + calls; }";
+
+    fn cached_resolver(key: PacCacheKey, env: Rc<PacEnvironment>) -> Resolver {
+        let resolver =
+            PACResolver::from_script("test.pac", COUNTING_SCRIPT.to_string(), vec![], env, false)
+                .unwrap()
+                .cache(key, Duration::from_secs(60));
+        Resolver::new(Box::new(resolver))
+    }
+
+    #[test]
+    fn test_cache_by_host() {
+        let resolver = cached_resolver(PacCacheKey::Host, Rc::default());
+        let answers: Vec<String> = [
+            "http://a.corp/x",
+            "http://a.corp:80/y?q",
+            "https://a.corp/x",
+            "http://b.corp/x",
+            "http://a.corp/z",
+        ]
+        .iter()
+        .map(|url| resolver.find_proxy(url).unwrap())
+        .collect();
+        assert_eq!(
+            answers,
+            [
+                "PROXY proxy:1",
+                "PROXY proxy:1",
+                "PROXY proxy:2",
+                "PROXY proxy:3",
+                "PROXY proxy:1"
+            ]
+        );
+        assert_eq!(
+            resolver.cache_stats(),
+            Some(CacheStats { hits: 2, misses: 3 })
+        );
+    }
+
+    #[test]
+    fn test_cache_by_url() {
+        let resolver = cached_resolver(PacCacheKey::Url, Rc::default());
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/x").unwrap(),
+            "PROXY proxy:1"
+        );
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/y").unwrap(),
+            "PROXY proxy:2"
+        );
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/x").unwrap(),
+            "PROXY proxy:1"
+        );
+
+        let resolver = cached_resolver(PacCacheKey::Off, Rc::default());
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/x").unwrap(),
+            "PROXY proxy:1"
+        );
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/x").unwrap(),
+            "PROXY proxy:2"
+        );
+        assert_eq!(resolver.cache_stats(), None);
+    }
+
+    #[test]
+    fn test_cache_cleared_by_environment() {
+        let env = Rc::new(PacEnvironment::new());
+        let resolver = cached_resolver(PacCacheKey::Host, env.clone());
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/").unwrap(),
+            "PROXY proxy:1"
+        );
+        env.set_my_ip(vec!["10.1.2.3".parse().unwrap()]);
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/").unwrap(),
+            "PROXY proxy:2"
+        );
+        assert_eq!(
+            resolver.find_proxy("http://a.corp/").unwrap(),
+            "PROXY proxy:2"
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, RwLock, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::pacloader::PacLoader;
+use crate::parser::{ProxyDirective, ProxyKind};
+use crate::{ResolveError, Resolver};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Time allowed to a client to send its request head
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Pause after a failed accept, like EMFILE, before trying again
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+// Largest request or response head accepted
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+// Headers for one connection only, not forwarded
+const HOP_BY_HOP_HEADERS: [&str; 6] = [
+    "connection",
+    "keep-alive",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "upgrade",
+];
+
+// Request from a connection thread to the resolver thread
+type ResolveRequest = (String, mpsc::Sender<Result<Vec<ProxyDirective>, String>>);
+
+struct RequestHead {
+    method: String,
+    target: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+// Read "METHOD target HTTP/1.1" line and headers, up to empty line
+fn read_head(reader: &mut impl BufRead) -> io::Result<RequestHead> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let mut size = 0;
+    let mut read_line = |reader: &mut dyn BufRead| -> io::Result<String> {
+        let mut line = String::new();
+        size += reader.read_line(&mut line)?;
+        if size > MAX_HEAD_SIZE {
+            return Err(invalid("request head too large"));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    };
+
+    let line = read_line(reader)?;
+    let mut fields = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (fields.next(), fields.next(), fields.next())
+    else {
+        return Err(invalid("invalid request line"));
+    };
+    let mut head = RequestHead {
+        method: method.to_ascii_uppercase(),
+        target: target.to_string(),
+        version: version.to_string(),
+        headers: vec![],
+    };
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            return Ok(head);
+        }
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| invalid("invalid header"))?;
+        head.headers
+            .push((name.trim().to_string(), value.trim().to_string()));
+    }
+}
+
+// Status line and headers of a response, up to empty line
+fn read_response_head(reader: &mut impl BufRead) -> io::Result<(u16, Vec<u8>)> {
+    let mut head = Vec::new();
+    loop {
+        let start = head.len();
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if head.len() > MAX_HEAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response head too large",
+            ));
+        }
+        if head[start..].trim_ascii().is_empty() {
+            break;
+        }
+    }
+    let status = String::from_utf8_lossy(&head)
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid status line"))?;
+    Ok((status, head))
+}
+
+fn connect(address: &str) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no address");
+    for socket_address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+// Copy bytes both ways until each side closes
+fn relay(client: TcpStream, upstream: TcpStream) -> io::Result<()> {
+    let (mut client_read, mut upstream_write) = (client.try_clone()?, upstream.try_clone()?);
+    let upload = thread::spawn(move || {
+        let _ = io::copy(&mut client_read, &mut upstream_write);
+        let _ = upstream_write.shutdown(Shutdown::Write);
+    });
+    let (mut upstream_read, mut client_write) = (upstream, client);
+    let _ = io::copy(&mut upstream_read, &mut client_write);
+    let _ = client_write.shutdown(Shutdown::Write);
+    let _ = upload.join();
+    Ok(())
+}
+
+// Accepted client with a timeout for its request head, none on transient
+// accept failure: a server keeps running after those
+fn accepted(client: io::Result<TcpStream>, verbose: bool) -> Option<TcpStream> {
+    match client.and_then(|client| {
+        client.set_read_timeout(Some(HEAD_TIMEOUT))?;
+        Ok(client)
+    }) {
+        Ok(client) => Some(client),
+        Err(e) => {
+            if verbose {
+                eprintln!(" ** ERROR : accept: {}", e);
+            }
+            thread::sleep(ACCEPT_RETRY_DELAY);
+            None
+        }
+    }
+}
+
+fn send_error(client: &mut TcpStream, status: &str, message: &str) {
+    let _ = write!(
+        client,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        message.len(),
+        message
+    );
+}
+
+// "host:port" of directive, none for kinds not supported by the server
+fn upstream_address(directive: &ProxyDirective) -> Option<String> {
+    match directive.kind {
+        ProxyKind::Proxy => Some(directive.address()),
+        _ => None,
+    }
+}
+
+// Request head for next hop, with "Connection: close"
+fn forward_head(head: &RequestHead, target: &str) -> Vec<u8> {
+    let mut out = format!("{} {} {}\r\n", head.method, target, head.version);
+    for (name, value) in &head.headers {
+        if !HOP_BY_HOP_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+            out.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    out.push_str("Connection: close\r\n\r\n");
+    out.into_bytes()
+}
+
+// Open tunnel to "host:port", directly or with CONNECT through proxy, none
+// when proxy refused it and client got its answer. Bytes
+// received from proxy after its answer are first bytes from origin, like a
+// server greeting: they are returned with the stream.
+fn open_tunnel(
+    directive: &ProxyDirective,
+    authority: &str,
+    client: &mut TcpStream,
+) -> io::Result<Option<(TcpStream, Vec<u8>)>> {
+    if directive.kind == ProxyKind::Direct {
+        return connect(authority).map(|stream| Some((stream, vec![])));
+    }
+    let Some(address) = upstream_address(directive) else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} proxy not supported", directive.kind.keyword()),
+        ));
+    };
+    let mut upstream = connect(&address)?;
+    write!(
+        upstream,
+        "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n",
+        authority, authority
+    )?;
+    let mut reader = BufReader::new(upstream.try_clone()?);
+    let (status, response) = read_response_head(&mut reader)?;
+    if (200..300).contains(&status) {
+        return Ok(Some((upstream, reader.buffer().to_vec())));
+    }
+    // Upstream proxy failed, next entry may work
+    if status >= 500 {
+        return Err(io::Error::other(format!(
+            "upstream proxy answered {}",
+            status
+        )));
+    }
+    // Upstream proxy refused, like 407 asking for credentials, client sees
+    // its answer
+    client.write_all(&response)?;
+    client.write_all(reader.buffer())?;
+    Ok(None)
+}
+
+fn handle_connect(
+    mut client: TcpStream,
+    head: &RequestHead,
+    pending: &[u8],
+    directives: &[ProxyDirective],
+    verbose: bool,
+) -> io::Result<()> {
+    for directive in directives {
+        match open_tunnel(directive, &head.target, &mut client) {
+            Ok(Some((mut upstream, early))) => {
+                if verbose {
+                    eprintln!("CONNECT {} via {}", head.target, directive);
+                }
+                client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?;
+                client.write_all(&early)?;
+                upstream.write_all(pending)?;
+                return relay(client, upstream);
+            }
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                if verbose {
+                    eprintln!("CONNECT {} via {}: {}", head.target, directive, e);
+                }
+            }
+        }
+    }
+    send_error(&mut client, "502 Bad Gateway", "no proxy available\n");
+    Ok(())
+}
+
+fn handle_request(
+    mut client: TcpStream,
+    head: &RequestHead,
+    pending: &[u8],
+    directives: &[ProxyDirective],
+    verbose: bool,
+) -> io::Result<()> {
+    let Ok(url) = url::Url::parse(&head.target) else {
+        send_error(&mut client, "400 Bad Request", "absolute URL expected\n");
+        return Ok(());
+    };
+    if url.scheme() != "http" {
+        send_error(&mut client, "501 Not Implemented", "only http URL\n");
+        return Ok(());
+    }
+    let authority = format!(
+        "{}:{}",
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or(80)
+    );
+    let path = &url[url::Position::BeforePath..url::Position::AfterQuery];
+
+    for directive in directives {
+        let (address, target) = match directive.kind {
+            ProxyKind::Direct => (Some(authority.clone()), path),
+            _ => (upstream_address(directive), head.target.as_str()),
+        };
+        let Some(address) = address else {
+            continue;
+        };
+        match connect(&address) {
+            Ok(mut upstream) => {
+                if verbose {
+                    eprintln!("{} {} via {}", head.method, head.target, directive);
+                }
+                upstream.write_all(&forward_head(head, target))?;
+                upstream.write_all(pending)?;
+                return relay(client, upstream);
+            }
+            Err(e) => {
+                if verbose {
+                    eprintln!("{} {} via {}: {}", head.method, head.target, directive, e);
+                }
+            }
+        }
+    }
+    send_error(&mut client, "502 Bad Gateway", "no proxy available\n");
+    Ok(())
+}
+
+fn handle_client(
+    client: TcpStream,
+    requests: mpsc::Sender<ResolveRequest>,
+    verbose: bool,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(client.try_clone()?);
+    let head = match read_head(&mut reader) {
+        Ok(head) => head,
+        Err(e) => {
+            send_error(
+                &mut client.try_clone()?,
+                "400 Bad Request",
+                &format!("{}\n", e),
+            );
+            return Ok(());
+        }
+    };
+    // Bytes already read after head belong to the request body or tunnel
+    let pending = reader.buffer().to_vec();
+    let mut client = reader.into_inner();
+    // Tunnels and request bodies may be idle for long
+    client.set_read_timeout(None)?;
+
+    let url = if head.method == "CONNECT" {
+        format!("https://{}/", head.target)
+    } else {
+        head.target.clone()
+    };
+    let (reply, answer) = mpsc::channel();
+    requests
+        .send((url, reply))
+        .map_err(|_| io::Error::other("resolver stopped"))?;
+    let directives = match answer.recv() {
+        Ok(Ok(directives)) => directives,
+        Ok(Err(message)) => {
+            send_error(&mut client, "502 Bad Gateway", &format!("{}\n", message));
+            return Ok(());
+        }
+        Err(_) => return Err(io::Error::other("resolver stopped")),
+    };
+
+    if head.method == "CONNECT" {
+        handle_connect(client, &head, &pending, &directives, verbose)
+    } else {
+        handle_request(client, &head, &pending, &directives, verbose)
+    }
+}
+
+/// Run an HTTP/1.1 forward proxy on listener, connecting directly or through
+/// the upstream proxy given by resolver for each request, with fallback
+/// entries tried in order. `CONNECT` tunnels and plain `http` requests are
+/// supported, with `PROXY` and `DIRECT` directives.
+///
+/// Connections are handled in threads, resolution is done in the calling
+/// thread, which never returns. Failures to accept a connection, like too
+/// many open files, are skipped, and clients have 30 seconds to send their
+/// request head.
+pub fn serve_proxy(listener: TcpListener, resolver: &Resolver, verbose: bool) -> io::Result<()> {
+    let (requests, incoming) = mpsc::channel::<ResolveRequest>();
+
+    let acceptor = thread::spawn(move || -> io::Result<()> {
+        for client in listener.incoming() {
+            let Some(client) = accepted(client, verbose) else {
+                continue;
+            };
+            let requests = requests.clone();
+            thread::spawn(move || {
+                if let Err(e) = handle_client(client, requests, verbose)
+                    && verbose
+                {
+                    eprintln!(" ** ERROR : {}", e);
+                }
+            });
+        }
+        Ok(())
+    });
+
+    for (url, reply) in incoming {
+        let _ = reply.send(resolver.resolve(&url).map_err(|e| e.to_string()));
+    }
+    acceptor
+        .join()
+        .unwrap_or_else(|_| Err(io::Error::other("proxy server stopped")))
+}
+
+// Paths of PAC script served by serve_pac
+const PAC_PATHS: [&str; 2] = ["/proxy.pac", "/wpad.dat"];
+
+const PAC_CONTENT_TYPE: &str = "application/x-ns-proxy-autoconfig";
+
+/// PAC script served by [`serve_pac`].
+pub enum PacSource {
+    /// Fixed script, like one generated from static proxy settings
+    Script(String),
+    /// Script loaded from a local file or URL, reloaded after each period
+    Remote {
+        location: String,
+        refresh: Duration,
+        loader: PacLoader,
+    },
+}
+
+fn handle_pac_client(client: TcpStream, script: &RwLock<String>) -> io::Result<()> {
+    let mut reader = BufReader::new(client.try_clone()?);
+    let mut client = client;
+    let head = match read_head(&mut reader) {
+        Ok(head) => head,
+        Err(e) => {
+            send_error(&mut client, "400 Bad Request", &format!("{}\n", e));
+            return Ok(());
+        }
+    };
+    if head.method != "GET" && head.method != "HEAD" {
+        send_error(&mut client, "405 Method Not Allowed", "GET expected\n");
+        return Ok(());
+    }
+    let path = head.target.split('?').next().unwrap_or_default();
+    if !PAC_PATHS.contains(&path) {
+        send_error(&mut client, "404 Not Found", "not found\n");
+        return Ok(());
+    }
+
+    let script = script
+        .read()
+        .map_err(|_| io::Error::other("PAC script lock"))?;
+    write!(
+        client,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        PAC_CONTENT_TYPE,
+        script.len()
+    )?;
+    if head.method == "GET" {
+        client.write_all(script.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Serve PAC script on listener, as `/proxy.pac` and `/wpad.dat`.
+///
+/// A remote script must load at start. On later failures, the last loaded
+/// script is served. Never returns unless the script fails to load at start,
+/// failures to accept a connection are skipped.
+pub fn serve_pac(
+    listener: TcpListener,
+    source: PacSource,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let script = match &source {
+        PacSource::Script(script) => script.clone(),
+        PacSource::Remote {
+            location, loader, ..
+        } => loader
+            .load(location)
+            .map_err(|source| ResolveError::PacUnavailable {
+                location: location.clone(),
+                source,
+            })?,
+    };
+    let script = Arc::new(RwLock::new(script));
+
+    if let PacSource::Remote {
+        location,
+        refresh,
+        loader,
+    } = source
+    {
+        let script = script.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(refresh);
+                match loader.load(&location) {
+                    Ok(update) => {
+                        if let Ok(mut script) = script.write() {
+                            *script = update;
+                        }
+                    }
+                    Err(e) => eprintln!(
+                        " ** WARNING : could not reload PAC script {}: {}, serving last one",
+                        location, e
+                    ),
+                }
+            }
+        });
+    }
+
+    for client in listener.incoming() {
+        let Some(client) = accepted(client, verbose) else {
+            continue;
+        };
+        let script = script.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle_pac_client(client, &script)
+                && verbose
+            {
+                eprintln!(" ** ERROR : {}", e);
+            }
+        });
+    }
+    Ok(())
+}
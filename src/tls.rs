@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::error::PacLoadError;
+
+/// TLS settings to download PAC scripts from `https://` URL.
+#[derive(Debug, Default)]
+pub struct TlsOptions {
+    /// PEM bundle of trusted CA certificates, replacing built-in roots
+    pub ca_file: Option<PathBuf>,
+    /// Accept any server certificate, for tests only
+    pub insecure: bool,
+    /// PEM client certificate chain, with `client_key`
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key of client certificate
+    pub client_key: Option<PathBuf>,
+}
+
+// Accepts any certificate, checking only handshake signatures
+#[derive(Debug)]
+struct InsecureVerifier(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for InsecureVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn tls_error(file: &Path, error: impl std::fmt::Display) -> PacLoadError {
+    PacLoadError::Tls(format!("{}: {}", file.display(), error))
+}
+
+impl TlsOptions {
+    /// Client configuration for ureq agent.
+    pub fn client_config(&self) -> Result<Arc<ClientConfig>, PacLoadError> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| PacLoadError::Tls(e.to_string()))?;
+
+        let builder = if self.insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(InsecureVerifier(provider)))
+        } else {
+            let mut roots = RootCertStore::empty();
+            match &self.ca_file {
+                Some(ca_file) => {
+                    for cert in
+                        CertificateDer::pem_file_iter(ca_file).map_err(|e| tls_error(ca_file, e))?
+                    {
+                        roots
+                            .add(cert.map_err(|e| tls_error(ca_file, e))?)
+                            .map_err(|e| tls_error(ca_file, e))?;
+                    }
+                    if roots.is_empty() {
+                        return Err(tls_error(ca_file, "no certificate found"));
+                    }
+                }
+                None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+            }
+            builder.with_root_certificates(roots)
+        };
+
+        let config = match (&self.client_cert, &self.client_key) {
+            (Some(cert_file), Some(key_file)) => {
+                let certs = CertificateDer::pem_file_iter(cert_file)
+                    .map_err(|e| tls_error(cert_file, e))?
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| tls_error(cert_file, e))?;
+                let key =
+                    PrivateKeyDer::from_pem_file(key_file).map_err(|e| tls_error(key_file, e))?;
+                builder
+                    .with_client_auth_cert(certs, key)
+                    .map_err(|e| tls_error(cert_file, e))?
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(PacLoadError::Tls(
+                    "client certificate and key go together".to_string(),
+                ));
+            }
+        };
+        Ok(Arc::new(config))
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use crate::pacloader::PacLoader;
+
+// Discovery tries several hosts, unreachable ones must fail fast
+const WPAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Search domain from "/etc/resolv.conf" content: first domain of last
+// "search" or "domain" line, as the resolver library does
+pub fn parse_resolv_conf(content: &str) -> Option<String> {
+    let mut domain = None;
+    for line in content.lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("search") | Some("domain") => {
+                domain = words
+                    .next()
+                    .map(|d| d.trim_end_matches('.').to_ascii_lowercase());
+            }
+            _ => {}
+        }
+    }
+    domain.filter(|domain| !domain.is_empty())
+}
+
+// Search domain of host, none when it cannot be found
+pub fn search_domain() -> Option<String> {
+    #[cfg(unix)]
+    {
+        parse_resolv_conf(&std::fs::read_to_string("/etc/resolv.conf").ok()?)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// WPAD URL tried for domain, from the longest domain down to the second
+/// level domain: "a.b.corp.com" gives "wpad.a.b.corp.com", "wpad.b.corp.com"
+/// then "wpad.corp.com".
+pub fn wpad_candidates(domain: &str) -> Vec<String> {
+    let domain = domain.trim().trim_matches('.').to_ascii_lowercase();
+    let labels: Vec<&str> = domain.split('.').filter(|l| !l.is_empty()).collect();
+    (0..labels.len().saturating_sub(1))
+        .map(|i| format!("http://wpad.{}/wpad.dat", labels[i..].join(".")))
+        .collect()
+}
+
+/// Web Proxy Auto-Discovery with DNS.
+pub struct Wpad {
+    loader: PacLoader,
+    verbose: bool,
+}
+
+impl Default for Wpad {
+    fn default() -> Self {
+        Self::new(PacLoader::default())
+    }
+}
+
+impl Wpad {
+    /// Discovery downloading candidates with loader, with its limits, TLS
+    /// settings, proxy and cache. Connect timeout is 2 seconds at most.
+    pub fn new(loader: PacLoader) -> Self {
+        Wpad {
+            loader: loader.max_connect_timeout(WPAD_CONNECT_TIMEOUT),
+            verbose: false,
+        }
+    }
+
+    /// Discovery with host names resolved by resolver, like
+    /// `|netloc: &str| Ok(vec![...])` where netloc is "host:port".
+    pub fn with_resolver(resolver: impl ureq::Resolver + 'static) -> Self {
+        Self::new(PacLoader::default().resolver(resolver))
+    }
+
+    /// Print tried URL on error output.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// URL and content of first reachable PAC script for domain.
+    pub fn discover(&self, domain: &str) -> Option<(String, String)> {
+        wpad_candidates(domain).into_iter().find_map(|url| {
+            let script = self.loader.load(&url);
+            if self.verbose {
+                match &script {
+                    Ok(_) => eprintln!("WPAD: {} (found)", url),
+                    Err(error) => eprintln!("WPAD: {} ({})", url, error),
+                }
+            }
+            Some(url).zip(script.ok())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let content = "# generated\nnameserver 10.0.0.1\nsearch Eng.Corp.Example.com. corp.example.com\noptions ndots:1\n";
+        assert_eq!(
+            parse_resolv_conf(content),
+            Some("eng.corp.example.com".to_string())
+        );
+        assert_eq!(
+            parse_resolv_conf("domain corp.example.com\nsearch lan.example.com\n"),
+            Some("lan.example.com".to_string())
+        );
+        assert_eq!(parse_resolv_conf("nameserver 10.0.0.1\n"), None);
+    }
+
+    #[test]
+    fn test_wpad_candidates() {
+        assert_eq!(
+            wpad_candidates("a.b.corp.com."),
+            [
+                "http://wpad.a.b.corp.com/wpad.dat",
+                "http://wpad.b.corp.com/wpad.dat",
+                "http://wpad.corp.com/wpad.dat",
+            ]
+        );
+        assert_eq!(
+            wpad_candidates("corp.com"),
+            ["http://wpad.corp.com/wpad.dat"]
+        );
+        assert!(wpad_candidates("corp").is_empty());
+        assert!(wpad_candidates("").is_empty());
+    }
+}
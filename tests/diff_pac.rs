@@ -0,0 +1,63 @@
+// Command diff-pac, run as a program with PAC files and URL corpus in
+// temporary directory.
+
+#![cfg(feature = "cli")]
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const OLD: &str = "function FindProxyForURL(url, host) { return \"PROXY proxy.corp:8080\"; }";
+
+// Same host routed by path
+const NEW: &str = "function FindProxyForURL(url, host) {
+    if (shExpMatch(url, \"*/api/*\")) return \"DIRECT\";
+    return \"PROXY proxy.corp:8080\";
+}";
+
+fn write(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("voluapt-diff-{}-{}", std::process::id(), name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+// Files are named after test, tests run in parallel
+fn diff_pac(test: &str, corpus: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_voluapt"))
+        .arg("diff-pac")
+        .arg(write(&format!("{}-old.pac", test), OLD))
+        .arg(write(&format!("{}-new.pac", test), NEW))
+        .arg("--urls")
+        .arg(write(&format!("{}-corpus.txt", test), corpus))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_paths_of_one_host() {
+    let corpus = "http://app.example.com/index.html\nhttp://app.example.com/api/users\n";
+    // Answer cache by host would reuse answer of first path
+    for args in [&[][..], &["--pac-cache", "host"]] {
+        let output = diff_pac("paths", corpus, args);
+        assert_eq!(output.status.code(), Some(12));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "http://app.example.com/api/users\tPROXY proxy.corp:8080\tDIRECT\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr).trim(),
+            "2 URL compared, 1 changed"
+        );
+    }
+}
+
+#[test]
+fn test_unchanged() {
+    let output = diff_pac(
+        "unchanged",
+        "# corpus\nhttp://www.example.com/\n",
+        &["--format", "json"],
+    );
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
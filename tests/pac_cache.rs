@@ -0,0 +1,109 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use voluapt::pacloader::PacLoader;
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"DIRECT\"; }";
+
+// PAC server with ETag "v1", answering 304 to matching conditional GET, for
+// at most `count` requests. Sends "If-None-Match" of each request on channel,
+// then "closed".
+fn start_server(cache_control: &'static str, count: usize) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/proxy.pac", listener.local_addr().unwrap());
+    let (conditions, received) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming().take(count) {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut condition = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':')
+                    && name.eq_ignore_ascii_case("If-None-Match")
+                {
+                    condition = value.trim().to_string();
+                }
+            }
+            let status = if condition == "\"v1\"" {
+                "304 Not Modified"
+            } else {
+                "200 OK"
+            };
+            let body = if condition == "\"v1\"" { "" } else { SCRIPT };
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nETag: \"v1\"\r\nCache-Control: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                cache_control,
+                body.len(),
+                body
+            )
+            .unwrap();
+            conditions.send(condition).unwrap();
+        }
+        drop(listener);
+        let _ = conditions.send("closed".to_string());
+    });
+    (url, received)
+}
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("voluapt-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_conditional_get() {
+    let (url, conditions) = start_server("max-age=0", 2);
+    let dir = cache_dir("conditional");
+    let loader = PacLoader::default().cache_dir(Some(dir.clone()));
+
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert_eq!(conditions.recv().unwrap(), "");
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert_eq!(conditions.recv().unwrap(), "\"v1\"");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fresh_copy() {
+    let (url, conditions) = start_server("max-age=3600", 2);
+    let dir = cache_dir("fresh");
+    let loader = PacLoader::default().cache_dir(Some(dir.clone()));
+
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert_eq!(conditions.recv().unwrap(), "");
+    assert!(conditions.try_recv().is_err());
+
+    // Refresh revalidates fresh copy
+    let loader = PacLoader::default()
+        .cache_dir(Some(dir.clone()))
+        .refresh(true);
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert_eq!(conditions.recv().unwrap(), "\"v1\"");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_offline_fallback() {
+    let (url, conditions) = start_server("no-cache", 1);
+    let dir = cache_dir("offline");
+    let loader = PacLoader::default().cache_dir(Some(dir.clone()));
+
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    conditions.recv().unwrap();
+    assert_eq!(conditions.recv().unwrap(), "closed");
+    assert_eq!(loader.load(&url).unwrap(), SCRIPT);
+    assert!(PacLoader::default().load(&url).is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
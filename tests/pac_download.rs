@@ -0,0 +1,137 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use voluapt::PacLoadError;
+use voluapt::pacloader::PacLoader;
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"DIRECT\"; }";
+
+// Server answering first request with head and body, returns PAC URL
+fn start_server(head: &str, body: Vec<u8>) -> String {
+    let mut response = format!(
+        "{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        head,
+        body.len()
+    )
+    .into_bytes();
+    response.extend(body);
+    start_raw_server(response)
+}
+
+// Server answering first request with response as is, then closing
+fn start_raw_server(response: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/proxy.pac", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        stream.write_all(&response).unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_status() {
+    let url = start_server("HTTP/1.1 404 Not Found", SCRIPT.into());
+    assert!(matches!(
+        PacLoader::default().load(&url),
+        Err(PacLoadError::Status(404))
+    ));
+}
+
+#[test]
+fn test_not_pac() {
+    let url = start_server(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/html",
+        b"<html>Sign in to continue</html>".to_vec(),
+    );
+    assert!(matches!(
+        PacLoader::default().load(&url),
+        Err(PacLoadError::NotPac)
+    ));
+}
+
+#[test]
+fn test_latin1() {
+    let mut body = b"// Proxy fran\xE7ais\n".to_vec();
+    body.extend_from_slice(SCRIPT.as_bytes());
+    let url = start_server(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/x-ns-proxy-autoconfig; charset=ISO-8859-1",
+        body,
+    );
+    let script = PacLoader::default().load(&url).unwrap();
+    assert!(script.starts_with("// Proxy français\n"));
+}
+
+#[test]
+fn test_gzip() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(SCRIPT.as_bytes()).unwrap();
+    let url = start_server(
+        "HTTP/1.1 200 OK\r\nContent-Encoding: gzip",
+        encoder.finish().unwrap(),
+    );
+    assert_eq!(PacLoader::default().load(&url).unwrap(), SCRIPT);
+}
+
+#[test]
+fn test_too_large() {
+    let url = start_server("HTTP/1.1 200 OK", SCRIPT.into());
+    assert!(matches!(
+        PacLoader::default().max_size(16).load(&url),
+        Err(PacLoadError::TooLarge { limit: 16 })
+    ));
+}
+
+#[test]
+fn test_truncated_chunked_body() {
+    // Connection closed before last chunk
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n",
+        SCRIPT.len(),
+        SCRIPT
+    );
+    let url = start_raw_server(response.into_bytes());
+    let dir = std::env::temp_dir().join(format!("voluapt-truncated-{}", std::process::id()));
+    let loader = PacLoader::default().cache_dir(Some(dir.clone()));
+    assert!(matches!(loader.load(&url), Err(PacLoadError::Truncated)));
+    // Not kept as last good copy
+    assert!(!dir.exists() || dir.read_dir().unwrap().next().is_none());
+}
+
+#[test]
+fn test_truncated_gzip_body() {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(SCRIPT.as_bytes()).unwrap();
+    let gzip = encoder.finish().unwrap();
+    // Without Content-Length, body ends when connection closes
+    let mut response =
+        b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nConnection: close\r\n\r\n".to_vec();
+    response.extend_from_slice(&gzip[..gzip.len() / 2]);
+    let url = start_raw_server(response);
+    assert!(matches!(
+        PacLoader::default().load(&url),
+        Err(PacLoadError::Truncated)
+    ));
+}
+
+#[test]
+fn test_read_timeout() {
+    // Server accepts connection, never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/proxy.pac", listener.local_addr().unwrap());
+    let loader = PacLoader::default().read_timeout(Duration::from_millis(100));
+    assert!(matches!(loader.load(&url), Err(PacLoadError::Transport(_))));
+    drop(listener);
+}
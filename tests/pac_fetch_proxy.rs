@@ -0,0 +1,207 @@
+// Local HTTP proxy stand-in: answers absolute-form requests itself, and
+// CONNECT requests as the HTTPS server for "localhost", with certificates of
+// tests/fixtures/tls.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use voluapt::PacLoadError;
+use voluapt::pacloader::PacLoader;
+use voluapt::tls::TlsOptions;
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"DIRECT\"; }";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn server_config() -> Arc<ServerConfig> {
+    let certs = CertificateDer::pem_file_iter(fixture("server.pem"))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_file(fixture("server.key")).unwrap();
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap();
+    Arc::new(config)
+}
+
+// Request head, from request line to empty line
+fn read_head(reader: &mut impl BufRead) -> Vec<String> {
+    let mut head = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line.trim() != "" {
+        head.push(line.trim().to_string());
+        line.clear();
+    }
+    head
+}
+
+fn send_script(stream: &mut impl Write) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        SCRIPT.len(),
+        SCRIPT
+    );
+    let _ = stream.flush();
+}
+
+fn handle(stream: TcpStream, config: Arc<ServerConfig>) -> Vec<String> {
+    let mut reader = BufReader::new(stream);
+    let mut head = read_head(&mut reader);
+    if !head
+        .first()
+        .is_some_and(|line| line.starts_with("CONNECT "))
+    {
+        send_script(reader.get_mut());
+        return head;
+    }
+    let mut stream = reader.into_inner();
+    let _ = write!(stream, "HTTP/1.1 200 Connection established\r\n\r\n");
+    let connection = ServerConnection::new(config).unwrap();
+    let mut tls = StreamOwned::new(connection, stream);
+    let mut reader = BufReader::new(&mut tls);
+    head.extend(read_head(&mut reader));
+    send_script(&mut tls);
+    tls.conn.send_close_notify();
+    let _ = tls.flush();
+    let _ = tls.sock.read(&mut [0; 1]);
+    head
+}
+
+// Proxy address, with request heads received
+fn start_proxy() -> (String, Receiver<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    let config = server_config();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let head = handle(stream.unwrap(), config.clone());
+            let _ = sender.lock().unwrap().send(head);
+        }
+    });
+    (address, receiver)
+}
+
+fn tls_loader() -> PacLoader {
+    let options = TlsOptions {
+        ca_file: Some(fixture("ca.pem")),
+        ..Default::default()
+    };
+    PacLoader::default()
+        .cache_dir(None)
+        .tls(options.client_config().unwrap())
+}
+
+#[test]
+fn test_http_through_proxy() {
+    let (address, requests) = start_proxy();
+    let loader = PacLoader::default()
+        .cache_dir(None)
+        .fetch_proxy(Some(format!("http://user:secret@{}", address)));
+    assert_eq!(loader.load("http://pac.test/proxy.pac").unwrap(), SCRIPT);
+
+    let head = requests.recv().unwrap();
+    assert_eq!(head[0], "GET http://pac.test/proxy.pac HTTP/1.1");
+    // "user:secret" in base64
+    assert!(
+        head.iter()
+            .any(|line| line == "Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=")
+    );
+}
+
+#[test]
+fn test_cached_by_proxy() {
+    let (address, _requests) = start_proxy();
+    let dir = std::env::temp_dir().join(format!("voluapt-by-proxy-{}", std::process::id()));
+    let loader = PacLoader::default()
+        .cache_dir(Some(dir.clone()))
+        .fetch_proxy(Some(format!("http://user:secret@{}", address)));
+    assert_eq!(loader.load("http://pac.test/proxy.pac").unwrap(), SCRIPT);
+
+    // Cache file names tell proxy address, never its credentials
+    let port = address.rsplit(':').next().unwrap();
+    let mut names: Vec<String> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    let name = format!("http___pac.test_proxy.pac_via_127.0.0.1_{}", port);
+    assert_eq!(names, [format!("{}.meta", name), format!("{}.pac", name)]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_https_through_connect() {
+    let (address, requests) = start_proxy();
+    let loader = tls_loader().fetch_proxy(Some(address));
+    assert_eq!(
+        loader.load("https://localhost:8443/proxy.pac").unwrap(),
+        SCRIPT
+    );
+
+    let head = requests.recv().unwrap();
+    assert_eq!(head[0], "CONNECT localhost:8443 HTTP/1.1");
+    // ureq sends absolute-form request in tunnel too
+    assert!(
+        head.iter()
+            .any(|line| line == "GET https://localhost:8443/proxy.pac HTTP/1.1")
+    );
+}
+
+#[test]
+fn test_invalid_proxy() {
+    let loader = PacLoader::default()
+        .cache_dir(None)
+        .fetch_proxy(Some("socks9://proxy:1080".to_string()));
+    assert!(matches!(
+        loader.load("http://pac.test/proxy.pac"),
+        Err(PacLoadError::Transport(_))
+    ));
+}
+
+// Runs program, built with cli feature only
+#[cfg(feature = "cli")]
+#[test]
+fn test_proxy_from_environment() {
+    let (address, requests) = start_proxy();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_voluapt"))
+        .args(["--pac", "https://localhost:8443/proxy.pac"])
+        .args(["--pac-fetch-proxy-env", "--no-cache"])
+        .arg("--cacert")
+        .arg(fixture("ca.pem"))
+        .arg("http://example.com/")
+        .env("https_proxy", &address)
+        .env_remove("HTTPS_PROXY")
+        .env_remove("no_proxy")
+        .env_remove("NO_PROXY")
+        .env_remove("VOLUAPT_CACERT")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "DIRECT");
+
+    let head = requests.recv().unwrap();
+    assert_eq!(head[0], "CONNECT localhost:8443 HTTP/1.1");
+}
@@ -0,0 +1,329 @@
+#![cfg(feature = "cli")]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use voluapt::server::serve_proxy;
+use voluapt::{PACResolver, Resolver};
+
+// Local forward proxy answering with PAC script, returns its address
+fn start_proxy(pac_script: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let resolver = Resolver::new(Box::new(
+            PACResolver::from_script("test.pac", pac_script, vec![], Rc::default(), false).unwrap(),
+        ));
+        serve_proxy(listener, &resolver, false).unwrap();
+    });
+    address
+}
+
+fn pac_returning(answer: &str) -> String {
+    format!(
+        "function FindProxyForURL(url, host) {{ return \"{}\"; }}",
+        answer
+    )
+}
+
+// Address of a closed port
+fn closed_port() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().to_string()
+}
+
+// Stand-in for origin servers and upstream proxies: sends first line of each
+// request on channel, then answers "200 OK" with body. For CONNECT, answers
+// "200 Connection established" then echoes bytes.
+fn start_stand_in(body: &'static str) -> (String, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let (lines, received) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let lines = lines.clone();
+            thread::spawn(move || {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                }
+                lines.send(request_line.trim_end().to_string()).unwrap();
+                if request_line.starts_with("CONNECT") {
+                    stream
+                        .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                        .unwrap();
+                    let mut buffer = [0; 4];
+                    reader.read_exact(&mut buffer).unwrap();
+                    stream.write_all(&buffer).unwrap();
+                } else {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .unwrap();
+                }
+            });
+        }
+    });
+    (address, received)
+}
+
+// Echo server for tunnels
+fn start_echo() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            thread::spawn(move || {
+                let mut buffer = [0; 4];
+                stream.read_exact(&mut buffer).unwrap();
+                stream.write_all(&buffer).unwrap();
+            });
+        }
+    });
+    address
+}
+
+// Upstream proxy answering CONNECT and sending origin greeting in one write,
+// like a SSH server through the tunnel
+fn start_greeting_upstream(greeting: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let answer = format!("HTTP/1.1 200 Connection established\r\n\r\n{}", greeting);
+            stream.write_all(answer.as_bytes()).unwrap();
+            // Keep tunnel open until client closes it
+            let _ = reader.read(&mut [0; 1]);
+        }
+    });
+    address
+}
+
+// Upstream proxy answering each request with status line and no body
+fn start_failing_upstream(status: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let answer = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+            stream.write_all(answer.as_bytes()).unwrap();
+        }
+    });
+    address
+}
+
+fn client(proxy: &str) -> TcpStream {
+    let stream = TcpStream::connect(proxy).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+}
+
+fn http_get(proxy: &str, url: &str) -> String {
+    let mut stream = client(proxy);
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: test\r\nProxy-Connection: keep-alive\r\n\r\n",
+        url
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+// Send CONNECT, then "ping" through tunnel, returns response head and echo
+fn tunnel(proxy: &str, authority: &str) -> (String, String) {
+    let stream = client(proxy);
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    write!(
+        writer,
+        "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n",
+        authority, authority
+    )
+    .unwrap();
+    let mut status = String::new();
+    reader.read_line(&mut status).unwrap();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+    }
+    if !status.contains(" 200 ") {
+        return (status, String::new());
+    }
+    writer.write_all(b"ping").unwrap();
+    let mut echo = [0; 4];
+    reader.read_exact(&mut echo).unwrap();
+    (status, String::from_utf8_lossy(&echo).to_string())
+}
+
+#[test]
+fn test_direct_request() {
+    let (origin, requests) = start_stand_in("origin");
+    let proxy = start_proxy(pac_returning("DIRECT"));
+
+    let response = http_get(&proxy, &format!("http://{}/path?q=1", origin));
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("origin"));
+    assert_eq!(requests.recv().unwrap(), "GET /path?q=1 HTTP/1.1");
+}
+
+#[test]
+fn test_request_through_upstream() {
+    let (upstream, requests) = start_stand_in("upstream");
+    let proxy = start_proxy(pac_returning(&format!("PROXY {}", upstream)));
+
+    let response = http_get(&proxy, "http://example.test/index.html");
+    assert!(response.ends_with("upstream"), "{}", response);
+    assert_eq!(
+        requests.recv().unwrap(),
+        "GET http://example.test/index.html HTTP/1.1"
+    );
+}
+
+#[test]
+fn test_request_fallback() {
+    let (upstream, requests) = start_stand_in("upstream");
+    let proxy = start_proxy(pac_returning(&format!(
+        "PROXY {}; SOCKS5 {}; PROXY {}",
+        closed_port(),
+        closed_port(),
+        upstream
+    )));
+
+    let response = http_get(&proxy, "http://example.test/");
+    assert!(response.ends_with("upstream"), "{}", response);
+    assert_eq!(
+        requests.recv().unwrap(),
+        "GET http://example.test/ HTTP/1.1"
+    );
+}
+
+#[test]
+fn test_request_no_proxy_available() {
+    let proxy = start_proxy(pac_returning(&format!("PROXY {}", closed_port())));
+
+    let response = http_get(&proxy, "http://example.test/");
+    assert!(response.starts_with("HTTP/1.1 502"), "{}", response);
+}
+
+#[test]
+fn test_connect_direct() {
+    let origin = start_echo();
+    let proxy = start_proxy(pac_returning("DIRECT"));
+
+    let (status, echo) = tunnel(&proxy, &origin);
+    assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+    assert_eq!(echo, "ping");
+}
+
+#[test]
+fn test_connect_through_upstream_with_fallback() {
+    let (upstream, requests) = start_stand_in("");
+    let proxy = start_proxy(pac_returning(&format!(
+        "PROXY {}; PROXY {}",
+        closed_port(),
+        upstream
+    )));
+
+    let (status, echo) = tunnel(&proxy, "example.test:443");
+    assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+    assert_eq!(echo, "ping");
+    assert_eq!(
+        requests.recv().unwrap(),
+        "CONNECT example.test:443 HTTP/1.1"
+    );
+}
+
+#[test]
+fn test_connect_fallback_after_upstream_error() {
+    let (upstream, requests) = start_stand_in("");
+    let proxy = start_proxy(pac_returning(&format!(
+        "PROXY {}; PROXY {}",
+        start_failing_upstream("503 Service Unavailable"),
+        upstream
+    )));
+
+    let (status, echo) = tunnel(&proxy, "example.test:443");
+    assert!(status.starts_with("HTTP/1.1 200"), "{}", status);
+    assert_eq!(echo, "ping");
+    assert_eq!(
+        requests.recv().unwrap(),
+        "CONNECT example.test:443 HTTP/1.1"
+    );
+}
+
+#[test]
+fn test_connect_upstream_refusal() {
+    let (upstream, requests) = start_stand_in("");
+    let proxy = start_proxy(pac_returning(&format!(
+        "PROXY {}; PROXY {}",
+        start_failing_upstream("407 Proxy Authentication Required"),
+        upstream
+    )));
+
+    let (status, _) = tunnel(&proxy, "example.test:443");
+    assert_eq!(status, "HTTP/1.1 407 Proxy Authentication Required\r\n");
+    assert!(requests.try_recv().is_err());
+}
+
+#[test]
+fn test_connect_server_speaks_first() {
+    let upstream = start_greeting_upstream("SSH-2.0-stand-in\r\n");
+    let proxy = start_proxy(pac_returning(&format!("PROXY {}", upstream)));
+
+    let stream = client(&proxy);
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    write!(
+        writer,
+        "CONNECT example.test:22 HTTP/1.1\r\nHost: example.test:22\r\n\r\n"
+    )
+    .unwrap();
+    let mut lines = Vec::new();
+    for _ in 0..3 {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        lines.push(line);
+    }
+    assert_eq!(
+        lines,
+        [
+            "HTTP/1.1 200 Connection established\r\n",
+            "\r\n",
+            "SSH-2.0-stand-in\r\n"
+        ]
+    );
+}
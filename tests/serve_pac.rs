@@ -0,0 +1,82 @@
+#![cfg(feature = "cli")]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use voluapt::pacloader::PacLoader;
+use voluapt::server::{PacSource, serve_pac};
+
+fn start_pac_server(source: PacSource) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    thread::spawn(move || serve_pac(listener, source, false).unwrap());
+    address
+}
+
+fn request(server: &str, method: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(server).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: test\r\n\r\n", method, path).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"DIRECT\"; }";
+
+#[test]
+fn test_serve_script() {
+    let server = start_pac_server(PacSource::Script(SCRIPT.to_string()));
+
+    for path in ["/proxy.pac", "/wpad.dat", "/proxy.pac?v=1"] {
+        let response = request(&server, "GET", path);
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("Content-Type: application/x-ns-proxy-autoconfig\r\n"));
+        assert!(response.ends_with(SCRIPT));
+    }
+
+    let response = request(&server, "HEAD", "/wpad.dat");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("\r\n\r\n"));
+
+    let response = request(&server, "GET", "/index.html");
+    assert!(response.starts_with("HTTP/1.1 404"), "{}", response);
+}
+
+#[test]
+fn test_serve_remote_refresh() {
+    let path = std::env::temp_dir().join(format!("voluapt-serve-pac-{}.pac", std::process::id()));
+    std::fs::write(&path, SCRIPT).unwrap();
+    let server = start_pac_server(PacSource::Remote {
+        location: path.to_string_lossy().to_string(),
+        refresh: Duration::from_millis(50),
+        loader: PacLoader::default(),
+    });
+    assert!(request(&server, "GET", "/proxy.pac").ends_with(SCRIPT));
+
+    // Last good copy is served while script is missing
+    std::fs::remove_file(&path).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert!(request(&server, "GET", "/proxy.pac").ends_with(SCRIPT));
+
+    let update = SCRIPT.replace("DIRECT", "PROXY proxy.corp:8080");
+    std::fs::write(&path, &update).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert!(request(&server, "GET", "/proxy.pac").ends_with(&update));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_serve_remote_unavailable() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let source = PacSource::Remote {
+        location: "/nonexistent/proxy.pac".to_string(),
+        refresh: Duration::from_secs(60),
+        loader: PacLoader::default(),
+    };
+    assert!(serve_pac(listener, source, false).is_err());
+}
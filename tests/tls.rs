@@ -0,0 +1,173 @@
+// Certificates in tests/fixtures/tls are issued by a test CA, generated with
+// openssl for "localhost" and 127.0.0.1, valid for 100 years.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+use rustls::crypto::ring;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use voluapt::pacloader::PacLoader;
+use voluapt::tls::TlsOptions;
+use voluapt::{PacLoadError, ResolveError};
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"DIRECT\"; }";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+// HTTPS server for PAC script, requiring a client certificate from test CA
+// when asked, returns PAC URL
+fn start_server(client_auth: bool) -> String {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if client_auth {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(fixture("ca.pem")).unwrap())
+            .unwrap();
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .unwrap();
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let certs = CertificateDer::pem_file_iter(fixture("server.pem"))
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let key = PrivateKeyDer::from_pem_file(fixture("server.key")).unwrap();
+    let config = Arc::new(builder.with_single_cert(certs, key).unwrap());
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}/proxy.pac",
+        listener.local_addr().unwrap().port()
+    );
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = ServerConnection::new(config.clone()).unwrap();
+            let mut stream = StreamOwned::new(connection, stream.unwrap());
+            // Rejected handshakes end here
+            let mut reader = BufReader::new(&mut stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).is_ok_and(|n| n > 0) && line.trim() != "" {
+                line.clear();
+            }
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                SCRIPT.len(),
+                SCRIPT
+            );
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+        }
+    });
+    url
+}
+
+fn loader(options: TlsOptions) -> PacLoader {
+    PacLoader::default().tls(options.client_config().unwrap())
+}
+
+#[test]
+fn test_private_ca() {
+    let url = start_server(false);
+
+    // Test CA is unknown to built-in roots
+    assert!(matches!(
+        loader(TlsOptions::default()).load(&url),
+        Err(PacLoadError::Transport(_))
+    ));
+
+    let options = TlsOptions {
+        ca_file: Some(fixture("ca.pem")),
+        ..Default::default()
+    };
+    assert_eq!(loader(options).load(&url).unwrap(), SCRIPT);
+
+    let options = TlsOptions {
+        insecure: true,
+        ..Default::default()
+    };
+    assert_eq!(loader(options).load(&url).unwrap(), SCRIPT);
+}
+
+#[test]
+fn test_insecure_not_cached() {
+    let url = start_server(false);
+    let dir = std::env::temp_dir().join(format!("voluapt-insecure-{}", std::process::id()));
+    let cached = || std::fs::read_dir(&dir).map_or(0, |entries| entries.count());
+
+    let options = TlsOptions {
+        insecure: true,
+        ..Default::default()
+    };
+    let insecure = PacLoader::default()
+        .cache_dir(Some(dir.clone()))
+        .insecure(true)
+        .tls(options.client_config().unwrap());
+    assert_eq!(insecure.load(&url).unwrap(), SCRIPT);
+    assert_eq!(cached(), 0);
+
+    let options = TlsOptions {
+        ca_file: Some(fixture("ca.pem")),
+        ..Default::default()
+    };
+    let verified = loader(options).cache_dir(Some(dir.clone()));
+    assert_eq!(verified.load(&url).unwrap(), SCRIPT);
+    assert_eq!(cached(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_client_certificate() {
+    let url = start_server(true);
+
+    let options = TlsOptions {
+        ca_file: Some(fixture("ca.pem")),
+        ..Default::default()
+    };
+    assert!(loader(options).load(&url).is_err());
+
+    let options = TlsOptions {
+        ca_file: Some(fixture("ca.pem")),
+        client_cert: Some(fixture("client.pem")),
+        client_key: Some(fixture("client.key")),
+        ..Default::default()
+    };
+    assert_eq!(loader(options).load(&url).unwrap(), SCRIPT);
+}
+
+#[test]
+fn test_invalid_options() {
+    let options = TlsOptions {
+        ca_file: Some(fixture("missing.pem")),
+        ..Default::default()
+    };
+    assert!(matches!(options.client_config(), Err(PacLoadError::Tls(_))));
+    let options = TlsOptions {
+        client_cert: Some(fixture("client.pem")),
+        ..Default::default()
+    };
+    let error = ResolveError::PacUnavailable {
+        location: "https://lan.corp/proxy.pac".to_string(),
+        source: options.client_config().unwrap_err(),
+    };
+    assert_eq!(
+        error.to_string(),
+        "could not load PAC script https://lan.corp/proxy.pac: TLS settings: client certificate and key go together"
+    );
+}
@@ -0,0 +1,119 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::thread;
+
+use voluapt::pacloader::PacLoader;
+use voluapt::wpad::Wpad;
+use voluapt::{ProxySettings, ResolverBuilder};
+
+const SCRIPT: &str = "function FindProxyForURL(url, host) { return \"PROXY wpad.test:8080\"; }";
+
+// HTTP server answering each request with status and body
+fn start_server(status: &'static str, body: &'static str) -> SocketAddr {
+    start_server_bytes(status, body.as_bytes())
+}
+
+fn start_server_bytes(status: &'static str, body: &'static [u8]) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+            }
+            write!(
+                stream,
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                status,
+                body.len(),
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+    address
+}
+
+// Resolver knowing "wpad.eng.corp.test", answering 404, and
+// "wpad.corp.test", serving PAC script. Other hosts do not exist.
+fn wpad() -> Wpad {
+    let not_found = start_server("404 Not Found", "");
+    let found = start_server("200 OK", SCRIPT);
+    Wpad::with_resolver(move |netloc: &str| match netloc {
+        "wpad.eng.corp.test:80" => Ok(vec![not_found]),
+        "wpad.corp.test:80" => Ok(vec![found]),
+        _ => Err(io::Error::new(io::ErrorKind::NotFound, netloc.to_string())),
+    })
+}
+
+#[test]
+fn test_discover() {
+    assert_eq!(
+        wpad().discover("host.eng.corp.test"),
+        Some((
+            "http://wpad.corp.test/wpad.dat".to_string(),
+            SCRIPT.to_string()
+        ))
+    );
+    assert_eq!(wpad().discover("eng.other.test"), None);
+}
+
+// Candidates are downloaded like other PAC files
+#[test]
+fn test_discover_with_loader() {
+    let latin1 = start_server_bytes(
+        "200 OK",
+        b"// Proxy du r\xE9seau\nfunction FindProxyForURL(url, host) { return \"DIRECT\"; }",
+    );
+    let resolver = move |_: &str| Ok(vec![latin1]);
+    let (url, script) = Wpad::with_resolver(resolver).discover("corp.test").unwrap();
+    assert_eq!(url, "http://wpad.corp.test/wpad.dat");
+    assert!(script.starts_with("// Proxy du r\u{E9}seau\n"));
+
+    let loader = PacLoader::default().max_size(16).resolver(resolver);
+    assert_eq!(Wpad::new(loader).discover("corp.test"), None);
+}
+
+#[test]
+fn test_builder_auto_detect() {
+    let settings = ProxySettings {
+        auto_detect: true,
+        ..Default::default()
+    };
+    let resolver = ResolverBuilder::new(settings)
+        .wpad(wpad())
+        .domain(Some("eng.corp.test".to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(resolver.kind(), "pac");
+    assert_eq!(
+        resolver.pac_source().as_deref(),
+        Some("http://wpad.corp.test/wpad.dat")
+    );
+    assert_eq!(
+        resolver.find_proxy("http://example.com/").unwrap(),
+        "PROXY wpad.test:8080"
+    );
+}
+
+#[test]
+fn test_builder_auto_detect_fallback() {
+    let settings = ProxySettings {
+        auto_detect: true,
+        proxy_enable: true,
+        proxy_server: Some("proxy.corp:8080".to_string()),
+        ..Default::default()
+    };
+    let resolver = ResolverBuilder::new(settings)
+        .wpad(wpad())
+        .domain(Some("other.test".to_string()))
+        .build()
+        .unwrap();
+    assert_eq!(resolver.kind(), "static");
+}